@@ -2,6 +2,7 @@ pub const ESC: &str = "\x1b[";
 
 pub const BOLD: &str = "\x1b[1m";
 pub const RESET: &str = "\x1b[0m";
+pub const REVERSE: &str = "\x1b[7m";
 pub const BLACK: &str = "\x1b[30m";
 pub const RED: &str = "\x1b[31m";
 pub const GREEN: &str = "\x1b[32m";
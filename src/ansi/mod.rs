@@ -1,9 +1,12 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD};
 use std::fmt::Display;
-use std::io::{Write, stdout};
+use std::io::{self, Write, stderr, stdout};
 pub mod colors;
 pub mod constants;
 
 pub const ESC: &str = "\x1b[";
+pub const OSC: &str = "\x1b]";
+const BEL: &str = "\x07";
 pub const CLEAR: &str = "\x1b[H\x1b[2J\x1b[3J";
 #[inline(always)]
 fn ansi(seq: &str) -> String {
@@ -28,6 +31,12 @@ pub enum Csi {
     El(EL),
     Hide,
     Show,
+    /// Moves the cursor to a row and column (both start at 1)
+    MoveTo(usize, usize),
+    /// Switches to the alternate screen buffer, what was on the screen comes back with
+    /// [Csi::LeaveAltScreen]
+    EnterAltScreen,
+    LeaveAltScreen,
 }
 
 impl Display for Csi {
@@ -42,6 +51,40 @@ impl Display for Csi {
             },
             Csi::Hide => write!(f, "{}", ansi("?25l")),
             Csi::Show => write!(f, "{}", ansi("?25h")),
+            Csi::MoveTo(row, column) => write!(f, "{}", ansi(&format!("{row};{column}H"))),
+            Csi::EnterAltScreen => write!(f, "{}", ansi("?1049h")),
+            Csi::LeaveAltScreen => write!(f, "{}", ansi("?1049l")),
+        }
+    }
+}
+
+/// Operating system commands
+pub enum Osc {
+    /// Puts the text on the clipboard (OSC 52), works over SSH and without X11 in terminals that
+    /// support it, an empty text clears the clipboard
+    SetClipboard(String),
+}
+
+impl Display for Osc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Osc::SetClipboard(text) => write!(f, "{OSC}52;c;{}{BEL}", STANDARD.encode(text)),
+        }
+    }
+}
+
+/// Writes an escape sequence to the terminal even when stdout and stderr are redirected (falls
+/// back to stderr when there's no `/dev/tty`)
+pub fn write_to_terminal(sequence: impl Display) -> io::Result<()> {
+    match std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => {
+            write!(tty, "{sequence}")?;
+            tty.flush()
+        }
+        Err(_) => {
+            let mut buf = stderr();
+            write!(buf, "{sequence}")?;
+            buf.flush()
         }
     }
 }
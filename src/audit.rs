@@ -0,0 +1,377 @@
+//! Checks the health of every password in a [PasswordArray]
+
+use crate::{
+    breach::BreachDatabase,
+    rotation::SECONDS_PER_DAY,
+    storage::{PasswordArray, unix_time},
+    strength::estimate,
+};
+use comfy_table::{ContentArrangement, Table};
+use secrecy::ExposeSecret;
+use serde_json::{Value, json};
+
+pub const DEFAULT_MAX_AGE_DAYS: u64 = 365;
+/// Passwords this many edits (or less) apart are near-duplicates
+const NEAR_DUPLICATE_DISTANCE: usize = 2;
+/// Shorter passwords are always a few edits apart so they aren't compared
+const MIN_NEAR_DUPLICATE_LENGTH: usize = 6;
+/// Passwords with a lower score (fair or worse) are reported as weak
+const MIN_SCORE: u8 = 3;
+
+/// Something wrong with a password
+#[derive(Debug, Clone)]
+pub enum Issue {
+    /// The same password is used by these services
+    Reused(Vec<String>),
+    /// Scored less than [MIN_SCORE], `strength` is the name of the score (like "fair")
+    Weak {
+        score: u8,
+        strength: &'static str,
+        warning: Option<String>,
+    },
+    /// Not changed in this many days
+    Old(u64),
+    MasterPassword,
+    /// Almost the same password is used by these services
+    NearDuplicate(Vec<String>),
+    /// Found this many times in the breach database
+    Breached(u64),
+}
+
+impl Issue {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Issue::Reused(_) => "reused",
+            Issue::Weak { .. } => "weak",
+            Issue::Old(_) => "old",
+            Issue::MasterPassword => "master password",
+            Issue::NearDuplicate(_) => "near duplicate",
+            Issue::Breached(_) => "breached",
+        }
+    }
+    fn description(&self) -> String {
+        match self {
+            Issue::Reused(services) => format!("Same password as {}", services.join(", ")),
+            Issue::Weak {
+                score,
+                strength,
+                warning,
+            } => match warning {
+                Some(warning) => format!("Only {strength} (score {score}/4): {warning}"),
+                None => format!("Only {strength} (score {score}/4)"),
+            },
+            Issue::Old(days) => format!("Not changed in {days} days"),
+            Issue::MasterPassword => String::from("Same as the master password"),
+            Issue::NearDuplicate(services) => {
+                format!("Almost the same as {}", services.join(", "))
+            }
+            Issue::Breached(count) => format!("Found {count} times in breaches"),
+        }
+    }
+    fn to_json(&self) -> Value {
+        let mut value = match self {
+            Issue::Reused(services) | Issue::NearDuplicate(services) => {
+                json!({ "services": services })
+            }
+            Issue::Weak { score, warning, .. } => json!({ "score": score, "warning": warning }),
+            Issue::Old(days) => json!({ "days": days }),
+            Issue::MasterPassword => json!({}),
+            Issue::Breached(count) => json!({ "count": count }),
+        };
+        value["type"] = json!(self.name());
+        value
+    }
+}
+
+/// The audit result of one service
+#[derive(Debug, Clone)]
+pub struct EntryReport {
+    pub service: String,
+    /// Unix time of the last password change (None if it wasn't tracked yet)
+    pub changed: Option<u64>,
+    pub issues: Vec<Issue>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditReport {
+    pub entries: Vec<EntryReport>,
+    pub max_age_days: u64,
+    pub checked_breaches: bool,
+}
+
+impl AuditReport {
+    /// Audits every service with a password (services that only have a 2FA secret are skipped)
+    pub fn run(
+        password_array: &PasswordArray,
+        max_age_days: u64,
+        breach_database: Option<&BreachDatabase>,
+    ) -> Result<AuditReport, String> {
+        let now = unix_time();
+        let mut passwords = vec![];
+        for service in password_array.get_services() {
+            let password = password_array.get_password(&service)?;
+            if !password.expose_secret().is_empty() {
+                passwords.push((service, password));
+            }
+        }
+        let mut entries = vec![];
+        for (service, password) in &passwords {
+            let mut issues = vec![];
+            let (reused, near_duplicates): (Vec<_>, Vec<_>) = passwords
+                .iter()
+                .filter(|(other, _)| other != service)
+                .filter_map(|(other, other_password)| {
+                    let distance = edit_distance(
+                        &password.expose_secret().to_lowercase(),
+                        &other_password.expose_secret().to_lowercase(),
+                    );
+                    let same = password.expose_secret() == other_password.expose_secret();
+                    let long_enough = password.expose_secret().chars().count()
+                        >= MIN_NEAR_DUPLICATE_LENGTH
+                        && other_password.expose_secret().chars().count()
+                            >= MIN_NEAR_DUPLICATE_LENGTH;
+                    (same || long_enough && distance <= NEAR_DUPLICATE_DISTANCE)
+                        .then(|| (other.clone(), same))
+                })
+                .partition(|(_, same)| *same);
+            if !reused.is_empty() {
+                issues.push(Issue::Reused(reused.into_iter().map(|(s, _)| s).collect()));
+            }
+            if !near_duplicates.is_empty() {
+                issues.push(Issue::NearDuplicate(
+                    near_duplicates.into_iter().map(|(s, _)| s).collect(),
+                ));
+            }
+            let strength = estimate(password.expose_secret());
+            if strength.score < MIN_SCORE {
+                issues.push(Issue::Weak {
+                    score: strength.score,
+                    strength: strength.score_name(),
+                    warning: strength.warning,
+                });
+            }
+            let changed = password_array.get_changed(service)?;
+            if let Some(changed) = changed {
+                let days = now.saturating_sub(changed) / SECONDS_PER_DAY;
+                if days >= max_age_days {
+                    issues.push(Issue::Old(days));
+                }
+            }
+            if password_array.is_master_password(password) {
+                issues.push(Issue::MasterPassword);
+            }
+            if let Some(breach_database) = breach_database {
+                let count = breach_database.count(password.expose_secret())?;
+                if count > 0 {
+                    issues.push(Issue::Breached(count));
+                }
+            }
+            entries.push(EntryReport {
+                service: service.clone(),
+                changed,
+                issues,
+            });
+        }
+        Ok(AuditReport {
+            entries,
+            max_age_days,
+            checked_breaches: breach_database.is_some(),
+        })
+    }
+    /// How many services have each kind of issue
+    pub fn counts(&self) -> Vec<(&'static str, usize)> {
+        let mut counts: Vec<(&'static str, usize)> = vec![];
+        for issue in self.entries.iter().flat_map(|entry| &entry.issues) {
+            match counts.iter_mut().find(|(name, _)| *name == issue.name()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((issue.name(), 1)),
+            }
+        }
+        counts
+    }
+    pub fn table(&self) -> Table {
+        let now = unix_time();
+        let mut rows = vec![];
+        for entry in &self.entries {
+            let changed = match entry.changed {
+                Some(changed) => {
+                    format!("{} days ago", now.saturating_sub(changed) / SECONDS_PER_DAY)
+                }
+                None => String::from("unknown"),
+            };
+            let issues = if entry.issues.is_empty() {
+                String::from("OK")
+            } else {
+                entry
+                    .issues
+                    .iter()
+                    .map(|issue| issue.description())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            rows.push(vec![entry.service.clone(), changed, issues]);
+        }
+        let mut table = Table::new();
+        table
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec!["Services", "Last changed", "Issues"])
+            .add_rows(rows);
+        table
+    }
+    /// One line like `3 of 10 services have issues (2 weak, 1 reused)`
+    pub fn summary(&self) -> String {
+        let with_issues = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.issues.is_empty())
+            .count();
+        let counts = self
+            .counts()
+            .iter()
+            .map(|(name, count)| format!("{count} {name}"))
+            .collect::<Vec<_>>();
+        let mut summary = format!(
+            "{with_issues} of {} services have issues",
+            self.entries.len()
+        );
+        if !counts.is_empty() {
+            summary.push_str(&format!(" ({})", counts.join(", ")));
+        }
+        summary
+    }
+    pub fn to_json(&self) -> String {
+        let entries: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "service": entry.service,
+                    "changed": entry.changed,
+                    "issues": entry.issues.iter().map(Issue::to_json).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let counts: serde_json::Map<String, Value> = self
+            .counts()
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), json!(count)))
+            .collect();
+        let report = json!({
+            "generated": unix_time(),
+            "max_age_days": self.max_age_days,
+            "checked_breaches": self.checked_breaches,
+            "entries": entries,
+            "counts": counts,
+        });
+        serde_json::to_string_pretty(&report).unwrap_or_default()
+    }
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::SecretString;
+
+    fn report(entries: &[(&str, &str)], max_age_days: u64) -> AuditReport {
+        let mut password_array =
+            PasswordArray::new(SecretString::from("master"), String::from("audit"));
+        for (service, password) in entries {
+            password_array
+                .add_password(service.to_string(), SecretString::from(*password))
+                .unwrap();
+        }
+        AuditReport::run(&password_array, max_age_days, None).unwrap()
+    }
+
+    fn issues(report: &AuditReport, service: &str) -> Vec<String> {
+        report
+            .entries
+            .iter()
+            .find(|entry| entry.service == service)
+            .unwrap()
+            .issues
+            .iter()
+            .map(Issue::description)
+            .collect()
+    }
+
+    #[test]
+    fn reused_and_near_duplicates() {
+        let report = report(
+            &[
+                ("a", "correct-horse-battery"),
+                ("b", "correct-horse-battery"),
+                ("c", "correct-horse-battery7"),
+                ("d", "mY9dog$bark"),
+            ],
+            DEFAULT_MAX_AGE_DAYS,
+        );
+        assert_eq!(
+            issues(&report, "a"),
+            ["Same password as b", "Almost the same as c"]
+        );
+        assert_eq!(issues(&report, "c"), ["Almost the same as a, b"]);
+        assert!(issues(&report, "d").is_empty());
+    }
+
+    #[test]
+    fn only_fair_or_worse_is_weak() {
+        let report = report(
+            &[("fair", "Summer2024!x"), ("good", "qZ7#mp2L")],
+            DEFAULT_MAX_AGE_DAYS,
+        );
+        assert!(issues(&report, "fair")[0].starts_with("Only fair (score 2/4)"));
+        assert!(issues(&report, "good").is_empty());
+    }
+
+    #[test]
+    fn old_and_master_password() {
+        let report = report(&[("a", "master"), ("b", "mY9dog$bark")], 0);
+        let a = issues(&report, "a");
+        assert!(a.contains(&String::from("Same as the master password")));
+        assert!(a.contains(&String::from("Not changed in 0 days")));
+        assert_eq!(issues(&report, "b"), ["Not changed in 0 days"]);
+    }
+
+    #[test]
+    fn summary_and_json() {
+        let report = report(
+            &[
+                ("a", "mY9dog$bark"),
+                ("b", "mY9dog$bark"),
+                ("c", "qZ7#mp2L"),
+            ],
+            DEFAULT_MAX_AGE_DAYS,
+        );
+        assert_eq!(report.summary(), "2 of 3 services have issues (2 reused)");
+        let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["counts"], json!({ "reused": 2 }));
+        assert_eq!(
+            json["entries"][0]["issues"][0],
+            json!({ "type": "reused", "services": ["b"] })
+        );
+        assert_eq!(json["max_age_days"], DEFAULT_MAX_AGE_DAYS);
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -0,0 +1,300 @@
+//! Offline lookups in a [Have I Been Pwned](https://haveibeenpwned.com/Passwords) password dump
+//!
+//! Nothing here touches the network, the dump has to be downloaded beforehand
+
+use sha1::{Digest, Sha1};
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Below this many bytes the binary search reads the remaining lines one by one
+const LINEAR_SCAN_SIZE: u64 = 4096;
+const PREFIX_LENGTH: usize = 5;
+
+/// A downloaded breach database
+#[derive(Debug, Clone)]
+pub enum BreachDatabase {
+    /// One file with `HASH:COUNT` lines sorted by hash (the "ordered by hash" download)
+    HashFile(String),
+    /// A directory with a file for every 5 character hash prefix (like `21BD1` or `21BD1.txt`)
+    /// containing `SUFFIX:COUNT` lines (what the range API returns)
+    RangeDirectory(String),
+}
+
+impl BreachDatabase {
+    /// Directories are range directories, files are sorted hash files
+    pub fn open(path: &str) -> Result<BreachDatabase, String> {
+        let metadata = fs::metadata(path)
+            .map_err(|err| format!("Can't open breach database {path}: {err}"))?;
+        if metadata.is_dir() {
+            Ok(BreachDatabase::RangeDirectory(path.to_string()))
+        } else {
+            Ok(BreachDatabase::HashFile(path.to_string()))
+        }
+    }
+    /// How many times `password` appears in breaches (0 if it's not in the database)
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::breach::BreachDatabase;
+    /// // "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+    /// std::fs::write("breaches.txt", "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\n").unwrap();
+    /// let database = BreachDatabase::open("breaches.txt").unwrap();
+    /// assert_eq!(database.count("password"), Ok(9545824));
+    /// assert_eq!(database.count("not in there"), Ok(0));
+    /// ```
+    pub fn count(&self, password: &str) -> Result<u64, String> {
+        let hash = sha1_hex(password);
+        match self {
+            BreachDatabase::HashFile(path) => search_hash_file(path, &hash),
+            BreachDatabase::RangeDirectory(path) => search_range_directory(path, &hash),
+        }
+    }
+}
+
+/// Uppercase hex SHA-1 like HIBP uses
+pub fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect()
+}
+
+/// Splits a `HASH:COUNT` line, a line without a count counts as 1
+fn parse_line(line: &str) -> Option<(String, u64)> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    match line.split_once(':') {
+        Some((hash, count)) => Some((hash.trim().to_uppercase(), count.trim().parse().ok()?)),
+        None => Some((line.to_uppercase(), 1)),
+    }
+}
+
+/// The first line that starts at or after `offset` and where it starts
+fn line_at(reader: &mut BufReader<File>, offset: u64) -> Result<Option<(u64, String)>, String> {
+    let error = |err: std::io::Error| format!("Error when reading breach database: {err}");
+    let mut start = offset;
+    if offset > 0 {
+        // the line before might end right before offset
+        reader.seek(SeekFrom::Start(offset - 1)).map_err(error)?;
+        let mut partial = vec![];
+        start = offset - 1 + reader.read_until(b'\n', &mut partial).map_err(error)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0)).map_err(error)?;
+    }
+    let mut line = String::new();
+    if reader.read_line(&mut line).map_err(error)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some((start, line)))
+}
+
+fn search_hash_file(path: &str, hash: &str) -> Result<u64, String> {
+    let file =
+        File::open(path).map_err(|err| format!("Can't open breach database {path}: {err}"))?;
+    let mut high = file
+        .metadata()
+        .map_err(|err| format!("Error when reading breach database: {err}"))?
+        .len();
+    let mut reader = BufReader::new(file);
+    // the line with the hash (if there is one) starts somewhere in low..high
+    let mut low = 0;
+    while high - low > LINEAR_SCAN_SIZE {
+        let middle = low + (high - low) / 2;
+        let Some((start, line)) = line_at(&mut reader, middle)? else {
+            high = middle;
+            continue;
+        };
+        if start >= high {
+            high = middle;
+            continue;
+        }
+        let Some((line_hash, count)) = parse_line(&line) else {
+            return Err(format!("Invalid line in breach database: {}", line.trim()));
+        };
+        match line_hash.as_str().cmp(hash) {
+            Ordering::Equal => return Ok(count),
+            Ordering::Less => low = start + 1,
+            Ordering::Greater => high = middle,
+        }
+    }
+    let mut offset = low;
+    while let Some((start, line)) = line_at(&mut reader, offset)? {
+        if start >= high {
+            break;
+        }
+        if let Some((line_hash, count)) = parse_line(&line)
+            && line_hash == hash
+        {
+            return Ok(count);
+        }
+        offset = start + line.len() as u64;
+    }
+    Ok(0)
+}
+
+fn search_range_directory(path: &str, hash: &str) -> Result<u64, String> {
+    let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+    let candidates = [
+        prefix.to_string(),
+        format!("{prefix}.txt"),
+        prefix.to_lowercase(),
+        format!("{}.txt", prefix.to_lowercase()),
+    ];
+    let Some(range_file) = candidates
+        .iter()
+        .map(|name| Path::new(path).join(name))
+        .find(|file| file.is_file())
+    else {
+        // a partial download just doesn't have this range
+        return Ok(0);
+    };
+    let contents = fs::read_to_string(&range_file).map_err(|err| {
+        format!(
+            "Can't read breach database range {}: {err}",
+            range_file.display()
+        )
+    })?;
+    Ok(contents
+        .lines()
+        .filter_map(parse_line)
+        .find(|(line_suffix, _)| line_suffix == suffix)
+        .map_or(0, |(_, count)| count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A directory in the system temp directory that's removed when it's dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("nspm_{name}_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+        fn join(&self, name: impl AsRef<str>) -> String {
+            self.0.join(name.as_ref()).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// "password0", "password1", ... sorted by hash with a count of their number + 1
+    fn hash_lines(amount: usize) -> Vec<(String, u64)> {
+        let mut lines: Vec<(String, u64)> = (0..amount)
+            .map(|index| (format!("password{index}"), index as u64 + 1))
+            .collect();
+        lines.sort_by_key(|(password, _)| sha1_hex(password));
+        lines
+    }
+
+    fn check_hash_file(line_ending: &str) {
+        let directory = TempDir::new(&format!("hash_file_{}", line_ending.len()));
+        let lines = hash_lines(1000);
+        let contents: String = lines
+            .iter()
+            .map(|(password, count)| format!("{}:{count}{line_ending}", sha1_hex(password)))
+            .collect();
+        assert!(contents.len() as u64 > 10 * LINEAR_SCAN_SIZE);
+        let path = directory.join("breaches.txt");
+        fs::write(&path, contents).unwrap();
+        let database = BreachDatabase::open(&path).unwrap();
+        assert!(matches!(database, BreachDatabase::HashFile(_)));
+        for index in [0, 1, lines.len() / 2, lines.len() - 2, lines.len() - 1] {
+            let (password, count) = &lines[index];
+            assert_eq!(database.count(password), Ok(*count), "line {index}");
+        }
+        for (password, count) in lines.iter() {
+            assert_eq!(database.count(password), Ok(*count));
+        }
+        assert_eq!(database.count("not in there"), Ok(0));
+        assert_eq!(database.count("password1000"), Ok(0));
+    }
+
+    #[test]
+    fn count_in_hash_file() {
+        check_hash_file("\n");
+    }
+
+    #[test]
+    fn count_in_hash_file_with_crlf() {
+        check_hash_file("\r\n");
+    }
+
+    #[test]
+    fn count_in_small_hash_file() {
+        let directory = TempDir::new("small_hash_file");
+        let path = directory.join("breaches.txt");
+        // "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        fs::write(&path, "5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8:9545824").unwrap();
+        let database = BreachDatabase::open(&path).unwrap();
+        assert_eq!(database.count("password"), Ok(9545824));
+        assert_eq!(database.count("not in there"), Ok(0));
+        fs::write(&path, "").unwrap();
+        assert_eq!(database.count("password"), Ok(0));
+    }
+
+    #[test]
+    fn count_in_range_directory() {
+        let directory = TempDir::new("range_directory");
+        let (upper, lower) = ("password", "hunter2");
+        let (upper_hash, lower_hash) = (sha1_hex(upper), sha1_hex(lower));
+        let (upper_prefix, upper_suffix) = upper_hash.split_at(PREFIX_LENGTH);
+        let (lower_prefix, lower_suffix) = lower_hash.split_at(PREFIX_LENGTH);
+        assert_ne!(upper_prefix, lower_prefix);
+        fs::write(
+            directory.join(upper_prefix),
+            format!("0000000000000000000000000000000000A:1\r\n{upper_suffix}:42\r\n"),
+        )
+        .unwrap();
+        fs::write(
+            directory.join(format!("{}.txt", lower_prefix.to_lowercase())),
+            format!("{}:7\n", lower_suffix.to_lowercase()),
+        )
+        .unwrap();
+        let database = BreachDatabase::open(directory.0.to_str().unwrap()).unwrap();
+        assert!(matches!(database, BreachDatabase::RangeDirectory(_)));
+        assert_eq!(database.count(upper), Ok(42));
+        assert_eq!(database.count(lower), Ok(7));
+        assert_eq!(database.count("not in there"), Ok(0));
+    }
+
+    #[test]
+    fn open_missing_database() {
+        assert!(BreachDatabase::open("/nonexistent/breaches.txt").is_err());
+    }
+
+    #[test]
+    fn sha1_hex_is_uppercase() {
+        assert_eq!(
+            sha1_hex("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+        assert_eq!(sha1_hex(""), "DA39A3EE5E6B4B0D3255BFEF95601890AFD80709");
+    }
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(
+            parse_line("1e4c9b93f3f0682250b6cf8331b7ee68fd8:3\r\n"),
+            Some((String::from("1E4C9B93F3F0682250B6CF8331B7EE68FD8"), 3))
+        );
+        assert_eq!(parse_line("ABCDEF"), Some((String::from("ABCDEF"), 1)));
+        assert_eq!(parse_line("ABCDEF:many"), None);
+        assert_eq!(parse_line("  \r\n"), None);
+    }
+}
@@ -0,0 +1,85 @@
+//! The config file (`~/.config/nspm/config` or `$XDG_CONFIG_HOME/nspm/config`)
+//!
+//! It has `key = value` lines, lines starting with `#` are comments:
+//! ```text
+//! # the vault used when there's no --directory
+//! directory = /home/me/vault
+//! breach_db = /home/me/pwned-passwords.txt
+//! # seconds until a copied password is cleared from the clipboard (0: never)
+//! clip_timeout = 45
+//! # minutes without a key until an unlocked vault is locked (0: never)
+//! lock_timeout = 10
+//! ```
+
+use std::{env, fs, path::PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// The vault used when there's no --directory
+    pub directory: Option<String>,
+    /// The breach database used when there's no --breach-db
+    pub breach_db: Option<String>,
+    /// Used when there's no --clip-timeout
+    pub clip_timeout: Option<u64>,
+    /// Used when there's no --lock-timeout
+    pub lock_timeout: Option<u64>,
+}
+
+impl Config {
+    /// Loads the config at `path` (or the default location), a missing file is an empty config
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let location = match path {
+            Some(path) => PathBuf::from(path),
+            None => match default_location() {
+                Some(location) => location,
+                None => return Ok(Config::default()),
+            },
+        };
+        if path.is_none() && !location.exists() {
+            return Ok(Config::default());
+        }
+        let contents = fs::read_to_string(&location)
+            .map_err(|err| format!("Failed to read {}, Error: {err}", location.display()))?;
+        Config::parse(&contents)
+    }
+    fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        for line in contents.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("Invalid config line: {line}"))?;
+            let value = value.trim().to_string();
+            match key.trim() {
+                "directory" => config.directory = Some(value),
+                "breach_db" => config.breach_db = Some(value),
+                "clip_timeout" => {
+                    config.clip_timeout = Some(
+                        value
+                            .parse()
+                            .map_err(|err| format!("Invalid clip_timeout {value}: {err}"))?,
+                    )
+                }
+                "lock_timeout" => {
+                    config.lock_timeout = Some(
+                        value
+                            .parse()
+                            .map_err(|err| format!("Invalid lock_timeout {value}: {err}"))?,
+                    )
+                }
+                other => return Err(format!("Unknown config key: {other}")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn default_location() -> Option<PathBuf> {
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(config_home) if !config_home.is_empty() => PathBuf::from(config_home),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(config_home.join("nspm").join("config"))
+}
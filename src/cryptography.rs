@@ -6,59 +6,163 @@ use base64::Engine as _;
 use base64::engine::general_purpose::STANDARD_NO_PAD;
 use base64::engine::general_purpose::URL_SAFE;
 use fernet::Fernet;
-use secrecy::SecretString;
+use secrecy::{ExposeSecret, SecretString};
 
-const MASTER_LENGTH: usize = 64;
-const MEMORY_COST: u32 = 2_u32.pow(16);
-const T_COST: u32 = 3;
-const P_COST: u32 = 1;
+use std::fmt::Display;
 
+const MASTER_LENGTH: usize = 64;
 const KEY_LENGTH: usize = 32;
-const MEMORY_COST_KEY: u32 = 2_u32.pow(16);
-const T_COST_KEY: u32 = 3;
-const P_COST_KEY: u32 = 1;
-
 const SALT_LENGTH: usize = 32;
 
-/// Hashes using Argon2id
+/// Argon2 variants that can derive the keys of a vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    Argon2id,
+    Argon2i,
+    Argon2d,
+}
+
+impl Kdf {
+    pub const NAMES: [&str; 3] = ["argon2id", "argon2i", "argon2d"];
+    pub fn parse(name: &str) -> Result<Kdf, String> {
+        match name.trim().to_lowercase().as_str() {
+            "argon2id" => Ok(Kdf::Argon2id),
+            "argon2i" => Ok(Kdf::Argon2i),
+            "argon2d" => Ok(Kdf::Argon2d),
+            other => Err(format!(
+                "Unknown KDF {other} (argon2id, argon2i or argon2d)"
+            )),
+        }
+    }
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            Kdf::Argon2id => Algorithm::Argon2id,
+            Kdf::Argon2i => Algorithm::Argon2i,
+            Kdf::Argon2d => Algorithm::Argon2d,
+        }
+    }
+}
+
+impl Display for Kdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.algorithm().as_str())
+    }
+}
+
+/// Ciphers that can encrypt the entries of a vault (only fernet for now)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Fernet,
+}
+
+impl Cipher {
+    pub const NAMES: [&str; 1] = ["fernet"];
+    pub fn parse(name: &str) -> Result<Cipher, String> {
+        match name.trim().to_lowercase().as_str() {
+            "fernet" => Ok(Cipher::Fernet),
+            other => Err(format!("Unknown cipher {other} (fernet)")),
+        }
+    }
+}
+
+impl Display for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cipher::Fernet => f.write_str("fernet"),
+        }
+    }
+}
+
+/// How the master password hash and the keys of the entries are derived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub kdf: Kdf,
+    /// In KiB
+    pub memory_cost: u32,
+    /// Amount of iterations
+    pub time_cost: u32,
+    /// Amount of lanes
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            kdf: Kdf::Argon2id,
+            memory_cost: 2_u32.pow(16),
+            time_cost: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Checks if argon2 accepts the parameters
+    pub fn validate(&self) -> Result<(), String> {
+        self.argon2_params(KEY_LENGTH)
+            .map(|_| ())
+            .map_err(|err| format!("Invalid KDF parameters: {err}"))
+    }
+    fn argon2_params(&self, length: usize) -> Result<argon2::Params, argon2::Error> {
+        argon2::Params::new(
+            self.memory_cost,
+            self.time_cost,
+            self.parallelism,
+            Some(length),
+        )
+    }
+}
+
+impl Display for KdfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} KiB memory, {} iterations, {} lanes)",
+            self.kdf, self.memory_cost, self.time_cost, self.parallelism
+        )
+    }
+}
+
+/// Hashes using Argon2 (the parameters are stored in the hash)
 ///
 /// # Example
 /// ```
-/// use nspm::cryptography::{hash, generate_salt};
+/// use nspm::cryptography::{hash, generate_salt, KdfParams};
 /// use argon2::password_hash::SaltString;
 /// let salt = SaltString::from_b64("677DhCspdGNHgyuHm+R3+5NU/0MRYDDw6AfgdPLMXeY").unwrap();
-/// let hashed = hash(b"paper", &salt).unwrap();
+/// let hashed = hash(b"paper", &salt, &KdfParams::default()).unwrap();
 /// ```
-pub fn hash(string: &[u8], salt: &SaltString) -> Result<String, argon2::password_hash::Error> {
+pub fn hash(
+    string: &[u8],
+    salt: &SaltString,
+    params: &KdfParams,
+) -> Result<String, argon2::password_hash::Error> {
     let argon = Argon2::new(
-        Algorithm::Argon2id,
+        params.kdf.algorithm(),
         argon2::Version::V0x13,
-        argon2::Params::new(MEMORY_COST, T_COST, P_COST, Some(MASTER_LENGTH))?,
+        params.argon2_params(MASTER_LENGTH)?,
     );
     let hash_output = argon.hash_password(string, salt)?.to_string();
     Ok(URL_SAFE.encode(hash_output))
 }
 
-/// Checks if `hash_check` is equal to the hash of string (with salt)
+/// Checks if `hash_check` is equal to the hash of string (with salt), the algorithm and parameters
+/// come from `hash_check`
 /// # Example
 /// ```
 /// use nspm::cryptography::check_hash;
-/// use nspm::cryptography::{hash, generate_salt};
+/// use nspm::cryptography::{hash, generate_salt, KdfParams};
 /// use argon2::password_hash::SaltString;
 /// let salt = SaltString::from_b64("677DhCspdGNHgyuHm+R3+5NU/0MRYDDw6AfgdPLMXeY").unwrap();
-/// let hashed = hash(b"paper", &salt).unwrap();
+/// let hashed = hash(b"paper", &salt, &KdfParams::default()).unwrap();
 /// assert!(check_hash("paper", &hashed))
 /// ```
 pub fn check_hash(string: &str, hash_check: &str) -> bool {
     let hash_check_decoded = String::from_utf8(URL_SAFE.decode(hash_check).unwrap()).unwrap();
     let parsed_hash = PasswordHash::new(&hash_check_decoded).unwrap();
-    Argon2::new(
-        Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        argon2::Params::new(MEMORY_COST, T_COST, P_COST, Some(MASTER_LENGTH)).unwrap(),
-    )
-    .verify_password(string.as_bytes(), &parsed_hash)
-    .is_ok()
+    Argon2::default()
+        .verify_password(string.as_bytes(), &parsed_hash)
+        .is_ok()
 }
 
 /// Generates a salt of length 32 (trust me i need this)
@@ -74,26 +178,14 @@ pub fn generate_salt(
 ///
 /// # Example
 /// ```
-/// use nspm::cryptography::encrypt;
+/// use nspm::cryptography::{encrypt, KdfParams};
 /// use argon2::password_hash::SaltString;
 /// let salt = SaltString::from_b64("/NQctu0+XVTdWle/+JlMdT2lE+wIxELEHqIBebsypek").unwrap();
 /// let master = b"p";
-/// encrypt(b"p", master, salt);
+/// encrypt(b"p", master, &salt, &KdfParams::default());
 /// ```
-pub fn encrypt(pwd: &[u8], master_pwd: &[u8], salt: &SaltString) -> String {
-    let mut key = [0u8; KEY_LENGTH];
-    let buffer = pwd;
-    let argon = Argon2::new_with_secret(
-        master_pwd,
-        Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        argon2::Params::new(MEMORY_COST_KEY, T_COST_KEY, P_COST_KEY, Some(KEY_LENGTH)).unwrap(),
-    )
-    .unwrap();
-    let _ = argon.hash_password_into(master_pwd, salt.as_str().as_bytes(), &mut key);
-    let key_b64 = URL_SAFE.encode(key);
-    let f = Fernet::new(key_b64.as_str()).unwrap();
-    f.encrypt(buffer)
+pub fn encrypt(pwd: &[u8], master_pwd: &[u8], salt: &SaltString, params: &KdfParams) -> String {
+    encrypt_with_key(pwd, &derive_key(master_pwd, salt, params))
 }
 
 /// Decrypts pwd using master_pwd
@@ -101,34 +193,54 @@ pub fn encrypt(pwd: &[u8], master_pwd: &[u8], salt: &SaltString) -> String {
 /// # Example:
 /// ```
 /// use nspm::cryptography::decrypt;
-/// use nspm::cryptography::encrypt;
+/// use nspm::cryptography::{encrypt, KdfParams};
 /// use secrecy::SecretString;
 /// use argon2::password_hash::SaltString;
 /// let salt = SaltString::from_b64("/NQctu0+XVTdWle/+JlMdT2lE+wIxELEHqIBebsypek").unwrap();
 /// let master = b"p";
-/// let fernet_encrypted = encrypt(b"p", master, &salt);
-/// decrypt(fernet_encrypted.as_bytes(), master, &salt);
+/// let fernet_encrypted = encrypt(b"p", master, &salt, &KdfParams::default());
+/// decrypt(fernet_encrypted.as_bytes(), master, &salt, &KdfParams::default());
 /// ```
 ///
 /// # Panics
 ///
 /// Panics if master_pwd is not correct.
-pub fn decrypt(pwd: &[u8], master_pwd: &[u8], salt: &SaltString) -> Result<SecretString, String> {
+pub fn decrypt(
+    pwd: &[u8],
+    master_pwd: &[u8],
+    salt: &SaltString,
+    params: &KdfParams,
+) -> Result<SecretString, String> {
+    let buffer_str = std::str::from_utf8(pwd).unwrap();
+    decrypt_with_key(buffer_str, &derive_key(master_pwd, salt, params))
+}
+
+/// Derives the fernet key [encrypt] and [decrypt] use, so a lot of things can be encrypted with
+/// the same key without deriving it every time
+pub fn derive_key(master_pwd: &[u8], salt: &SaltString, params: &KdfParams) -> SecretString {
     let mut key = [0u8; KEY_LENGTH];
-    let buffer = pwd;
     let argon = Argon2::new_with_secret(
         master_pwd,
-        Algorithm::Argon2id,
+        params.kdf.algorithm(),
         argon2::Version::V0x13,
-        argon2::Params::new(MEMORY_COST_KEY, T_COST_KEY, P_COST_KEY, Some(KEY_LENGTH)).unwrap(),
+        params.argon2_params(KEY_LENGTH).unwrap(),
     )
     .unwrap();
     let _ = argon.hash_password_into(master_pwd, salt.as_str().as_bytes(), &mut key);
-    let key_b64 = URL_SAFE.encode(key);
-    let f = Fernet::new(key_b64.as_str()).unwrap();
-    let buffer_str = std::str::from_utf8(buffer).unwrap();
+    SecretString::from(URL_SAFE.encode(key))
+}
+
+/// Encrypts `data` with a key from [derive_key]
+pub fn encrypt_with_key(data: &[u8], key: &SecretString) -> String {
+    let f = Fernet::new(key.expose_secret()).unwrap();
+    f.encrypt(data)
+}
+
+/// Decrypts `data` with a key from [derive_key]
+pub fn decrypt_with_key(data: &str, key: &SecretString) -> Result<SecretString, String> {
+    let f = Fernet::new(key.expose_secret()).unwrap();
     let decrypted = f
-        .decrypt(buffer_str)
+        .decrypt(data)
         .map_err(|e| format!("Error with decryption: {e}"))?;
     let decrypted_str = String::from_utf8(decrypted).unwrap();
     Ok(SecretString::from(decrypted_str))
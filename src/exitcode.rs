@@ -0,0 +1,21 @@
+//! The exit codes of nspm, scripts can rely on these staying the same
+//!
+//! 0 is success, clap also exits with [USAGE] when the arguments are invalid
+
+/// Anything that doesn't have its own code
+pub const ERROR: i32 = 1;
+/// Invalid arguments, config or missing --directory
+pub const USAGE: i32 = 2;
+/// The master password is incorrect
+pub const WRONG_PASSWORD: i32 = 3;
+/// The vault or service doesn't exist
+pub const NOT_FOUND: i32 = 4;
+/// Another nspm is saving the vault (or crashed while saving, then its temporary directory has to
+/// be removed)
+pub const LOCKED: i32 = 5;
+/// The vault doesn't have the right structure or something in it can't be decrypted
+pub const CORRUPT: i32 = 6;
+/// The service or vault already exists
+pub const CONFLICT: i32 = 7;
+/// Reading or writing a file failed (like missing permissions or a full disk)
+pub const IO: i32 = 8;
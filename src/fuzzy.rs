@@ -0,0 +1,107 @@
+//! Fuzzy matching of a query against service names (the characters of the query have to appear in
+//! order, but not next to each other)
+
+/// Every matched character
+const MATCH: i64 = 16;
+/// A match right after the previous one
+const CONSECUTIVE_BONUS: i64 = 12;
+/// A match at the start of the name or of a word in it
+const WORD_START_BONUS: i64 = 8;
+/// Every skipped character between two matches
+const GAP_PENALTY: i64 = 2;
+/// How much an entry changed in the last day, week or month gains (less than a [MATCH] so
+/// recency only decides between similar matches)
+const RECENCY_BONUS: [(u64, i64); 3] = [(86400, 6), (7 * 86400, 4), (30 * 86400, 2)];
+
+/// Scores how well `query` matches `candidate` (case-insensitive, whitespace in the query is
+/// ignored), None if it doesn't match, otherwise the score (higher is better) and the character
+/// positions in `candidate` that matched
+///
+/// # Example
+/// ```
+/// use nspm::fuzzy::score;
+/// assert!(score("gh", "github").unwrap().0 > score("gh", "digital-health").unwrap().0);
+/// assert_eq!(score("gh", "github").unwrap().1, vec![0, 3]);
+/// assert_eq!(score("xyz", "github"), None);
+/// ```
+pub fn score(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lowercase)
+        .collect();
+    let characters: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = characters.iter().copied().map(lowercase).collect();
+    if query.is_empty() {
+        return Some((0, vec![]));
+    }
+    // the first place where the whole query matched
+    let mut query_index = 0;
+    let mut end = None;
+    for (index, character) in lower.iter().enumerate() {
+        if *character == query[query_index] {
+            query_index += 1;
+            if query_index == query.len() {
+                end = Some(index);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    // going backwards from there gives the shortest match
+    let mut positions = vec![];
+    let mut query_index = query.len();
+    for index in (0..=end).rev() {
+        if query_index > 0 && lower[index] == query[query_index - 1] {
+            positions.push(index);
+            query_index -= 1;
+        }
+    }
+    positions.reverse();
+    let mut score = 0;
+    for (i, position) in positions.iter().enumerate() {
+        score += MATCH;
+        if is_word_start(&characters, *position) {
+            score += WORD_START_BONUS;
+        }
+        if i > 0 {
+            let gap = (position - positions[i - 1] - 1) as i64;
+            if gap == 0 {
+                score += CONSECUTIVE_BONUS;
+            } else {
+                score -= gap * GAP_PENALTY;
+            }
+        }
+    }
+    // less left over is a closer match
+    score -= (characters.len() - positions.len()) as i64 / 4;
+    Some((score, positions))
+}
+
+fn lowercase(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+/// The start of the name, after a separator or an uppercase letter after a lowercase one
+fn is_word_start(characters: &[char], position: usize) -> bool {
+    let Some(previous) = position.checked_sub(1).map(|index| characters[index]) else {
+        return true;
+    };
+    !previous.is_alphanumeric()
+        || previous.is_lowercase() && characters[position].is_uppercase()
+        || previous.is_alphabetic() != characters[position].is_alphabetic()
+}
+
+/// The bonus an entry last changed at `changed` (unix time) gets at `now`, recently changed
+/// entries are more likely to be the ones being looked for
+pub fn recency_bonus(changed: Option<u64>, now: u64) -> i64 {
+    let Some(changed) = changed else {
+        return 0;
+    };
+    let age = now.saturating_sub(changed);
+    RECENCY_BONUS
+        .iter()
+        .find(|(max_age, _)| age <= *max_age)
+        .map(|(_, bonus)| *bonus)
+        .unwrap_or_default()
+}
@@ -0,0 +1,776 @@
+use crate::passwordrules::PasswordRules;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use std::collections::HashSet;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+const LOOK_ALIKES: &str = "0O1lI";
+const CONSONANTS: &str = "bcdfghjklmnpqrstvwxyz";
+const VOWELS: &str = "aeiou";
+/// Start of a syllable, only sounds that are hard to mishear
+const ONSETS: [&str; 20] = [
+    "b", "d", "f", "g", "h", "j", "k", "l", "m", "n", "p", "r", "s", "t", "v", "w", "z", "ch",
+    "sh", "th",
+];
+/// End of a syllable, "" means the syllable ends with the vowel
+const CODAS: [&str; 7] = ["", "n", "r", "s", "l", "m", "t"];
+/// The EFF large wordlist for five dice (7776 words)
+const EFF_LARGE: &str = include_str!("wordlists/eff_large_wordlist.txt");
+/// The first EFF short wordlist for four dice (1296 words of at most 5 letters)
+const EFF_SHORT: &str = include_str!("wordlists/eff_short_wordlist_1.txt");
+
+/// Gives up on policies that almost never produce a valid password
+const MAX_ATTEMPTS: u32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Digits,
+    Symbols,
+}
+
+impl CharClass {
+    pub const ALL: [CharClass; 4] = [
+        CharClass::Lowercase,
+        CharClass::Uppercase,
+        CharClass::Digits,
+        CharClass::Symbols,
+    ];
+    pub fn chars(&self) -> &'static str {
+        match self {
+            CharClass::Lowercase => LOWERCASE,
+            CharClass::Uppercase => UPPERCASE,
+            CharClass::Digits => DIGITS,
+            CharClass::Symbols => SYMBOLS,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            CharClass::Lowercase => "lowercase letters",
+            CharClass::Uppercase => "uppercase letters",
+            CharClass::Digits => "digits",
+            CharClass::Symbols => "symbols",
+        }
+    }
+    /// The letter used for this class in prompts (l, u, d, s)
+    pub fn letter(&self) -> char {
+        match self {
+            CharClass::Lowercase => 'l',
+            CharClass::Uppercase => 'u',
+            CharClass::Digits => 'd',
+            CharClass::Symbols => 's',
+        }
+    }
+}
+
+/// Makes a password of `length` with at least the minimum amount of characters from every
+/// required set and the rest from `everything`, retrying until `is_allowed` accepts it
+///
+/// The minimums are placed first and then everything is shuffled. Every draw uses
+/// `random_range` so there's no modulo bias
+pub fn generate_from(
+    length: usize,
+    required: &[(Vec<char>, usize)],
+    everything: &[char],
+    is_allowed: impl Fn(&[char]) -> bool,
+) -> Result<String, String> {
+    if everything.is_empty() || required.iter().any(|(set, _)| set.is_empty()) {
+        return Err(String::from("There are no characters to choose from"));
+    }
+    let minimum_total: usize = required.iter().map(|(_, m)| m).sum();
+    if minimum_total > length {
+        return Err(format!(
+            "The minimums add up to {minimum_total} characters but the length is {length}"
+        ));
+    }
+    let mut rng = StdRng::from_os_rng();
+    for _ in 0..MAX_ATTEMPTS {
+        let mut password = vec![];
+        for (set, minimum) in required {
+            for _ in 0..*minimum {
+                password.push(set[rng.random_range(..set.len())]);
+            }
+        }
+        while password.len() < length {
+            password.push(everything[rng.random_range(..everything.len())]);
+        }
+        password.shuffle(&mut rng);
+        if is_allowed(&password) {
+            return Ok(password.into_iter().collect());
+        }
+    }
+    Err(String::from(
+        "Couldn't generate a password that follows the policy, try loosening it",
+    ))
+}
+
+/// The different ways of generating a password
+#[derive(Debug, Clone)]
+pub enum Generator {
+    Random(PasswordPolicy),
+    Passphrase(PassphrasePolicy),
+    Rules(PasswordRules),
+    Pattern(PatternPolicy),
+    Pronounceable(PronounceablePolicy),
+}
+
+impl Generator {
+    pub fn generate(&self) -> Result<String, String> {
+        match self {
+            Generator::Random(policy) => policy.generate(),
+            Generator::Passphrase(policy) => policy.generate(),
+            Generator::Rules(rules) => rules.generate(),
+            Generator::Pattern(policy) => policy.generate(),
+            Generator::Pronounceable(policy) => policy.generate(),
+        }
+    }
+    /// Roughly how many bits of entropy a generated password has
+    pub fn entropy(&self) -> f64 {
+        match self {
+            Generator::Random(policy) => policy.entropy(),
+            Generator::Passphrase(policy) => policy.entropy(),
+            Generator::Rules(rules) => rules.entropy(),
+            Generator::Pattern(policy) => policy.entropy(),
+            Generator::Pronounceable(policy) => policy.entropy(),
+        }
+    }
+}
+
+/// Rules for [generate][PasswordPolicy::generate]
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    /// Included character classes with the minimum amount of characters from each
+    pub classes: Vec<(CharClass, usize)>,
+    pub exclude: String,
+    /// Leaves out 0, O, 1, l and I
+    pub no_look_alikes: bool,
+    /// No same character twice in a row
+    pub no_repeats: bool,
+    /// No runs of 3 like "abc", "321" or "xyz"
+    pub no_sequences: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: 14,
+            classes: CharClass::ALL.iter().map(|c| (*c, 1)).collect(),
+            exclude: String::new(),
+            no_look_alikes: false,
+            no_repeats: false,
+            no_sequences: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// The characters of a class that are left after the exclusions
+    fn alphabet(&self, class: CharClass) -> Vec<char> {
+        class
+            .chars()
+            .chars()
+            .filter(|c| !self.exclude.contains(*c))
+            .filter(|c| !(self.no_look_alikes && LOOK_ALIKES.contains(*c)))
+            .collect()
+    }
+    /// Generates a password that follows the policy
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::generator::{CharClass, PasswordPolicy};
+    /// let policy = PasswordPolicy {
+    ///     length: 20,
+    ///     classes: vec![(CharClass::Digits, 5), (CharClass::Lowercase, 1)],
+    ///     no_look_alikes: true,
+    ///     ..Default::default()
+    /// };
+    /// let password = policy.generate().unwrap();
+    /// assert_eq!(password.len(), 20);
+    /// assert!(password.chars().filter(|c| c.is_ascii_digit()).count() >= 5);
+    /// assert!(!password.contains(['0', '1', 'l']));
+    /// ```
+    pub fn generate(&self) -> Result<String, String> {
+        let mut alphabets = vec![];
+        for (class, minimum) in &self.classes {
+            let alphabet = self.alphabet(*class);
+            if alphabet.is_empty() {
+                return Err(format!("Every one of the {} is excluded", class.name()));
+            }
+            alphabets.push((alphabet, *minimum));
+        }
+        let everything: Vec<char> = alphabets.iter().flat_map(|(a, _)| a.clone()).collect();
+        if everything.is_empty() {
+            return Err(String::from("The policy doesn't include any characters"));
+        }
+        generate_from(self.length, &alphabets, &everything, |password| {
+            self.is_allowed(password)
+        })
+    }
+    /// length * log2(alphabet size), ignoring the small loss from the other rules
+    pub fn entropy(&self) -> f64 {
+        let size: usize = self
+            .classes
+            .iter()
+            .map(|(class, _)| self.alphabet(*class).len())
+            .sum();
+        if size == 0 {
+            return 0.0;
+        }
+        self.length as f64 * (size as f64).log2()
+    }
+    fn is_allowed(&self, password: &[char]) -> bool {
+        if self.no_repeats && password.windows(2).any(|w| w[0] == w[1]) {
+            return false;
+        }
+        if self.no_sequences
+            && password.windows(3).any(|w| {
+                let (a, b, c) = (w[0] as i32, w[1] as i32, w[2] as i32);
+                w.iter().all(|ch| ch.is_ascii_alphanumeric())
+                    && b - a == c - b
+                    && (b - a).abs() == 1
+            })
+        {
+            return false;
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capitalization {
+    Lower,
+    Upper,
+    /// First letter of every word
+    Title,
+    /// Every word is randomly either lowercase or title case
+    Random,
+}
+
+impl Capitalization {
+    pub const NAMES: [&str; 4] = ["lower", "upper", "title", "random"];
+    pub fn parse(name: &str) -> Result<Capitalization, String> {
+        match name.trim().to_lowercase().as_str() {
+            "lower" => Ok(Capitalization::Lower),
+            "upper" => Ok(Capitalization::Upper),
+            "title" => Ok(Capitalization::Title),
+            "random" => Ok(Capitalization::Random),
+            other => Err(format!("Unknown capitalization: {other}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Wordlist {
+    /// [EFF_LARGE], 12.9 bits per word
+    Large,
+    /// [EFF_SHORT], 10.3 bits per word but easier to type on a TV remote
+    Short,
+    Custom(Vec<String>),
+}
+
+impl Wordlist {
+    /// Parses "large", "short" or reads a file with one word per line. Lines of the EFF
+    /// wordlists ("11111\tabacus") work too since only the last word of a line is used
+    pub fn parse(name: &str) -> Result<Wordlist, String> {
+        match name.trim() {
+            "large" => Ok(Wordlist::Large),
+            "short" => Ok(Wordlist::Short),
+            path => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Failed to read {path}, Error: {err}"))?;
+                let mut seen = HashSet::new();
+                let words: Vec<String> = last_words(&contents)
+                    .filter(|word| seen.insert(word.to_string()))
+                    .map(|word| word.to_string())
+                    .collect();
+                if words.len() < 2 {
+                    return Err(format!("{path} doesn't have enough words"));
+                }
+                Ok(Wordlist::Custom(words))
+            }
+        }
+    }
+    pub fn words(&self) -> Vec<&str> {
+        match self {
+            Wordlist::Large => last_words(EFF_LARGE).collect(),
+            Wordlist::Short => last_words(EFF_SHORT).collect(),
+            Wordlist::Custom(words) => words.iter().map(|w| w.as_str()).collect(),
+        }
+    }
+}
+
+/// The last word of every line, skipping the dice numbers of the EFF wordlists
+fn last_words(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+}
+
+/// Rules for [generate][PassphrasePolicy::generate]
+#[derive(Debug, Clone)]
+pub struct PassphrasePolicy {
+    pub words: usize,
+    pub separator: String,
+    pub capitalization: Capitalization,
+    /// Puts a random digit at the end of a random word
+    pub digit: bool,
+    /// Puts a random symbol at the end of a random word
+    pub symbol: bool,
+    pub wordlist: Wordlist,
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> Self {
+        Self {
+            words: 6,
+            separator: String::from("-"),
+            capitalization: Capitalization::Lower,
+            digit: false,
+            symbol: false,
+            wordlist: Wordlist::Large,
+        }
+    }
+}
+
+impl PassphrasePolicy {
+    /// Generates a diceware style passphrase
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::generator::{Capitalization, PassphrasePolicy};
+    /// let policy = PassphrasePolicy {
+    ///     words: 4,
+    ///     separator: String::from(" "),
+    ///     capitalization: Capitalization::Title,
+    ///     ..Default::default()
+    /// };
+    /// let passphrase = policy.generate().unwrap();
+    /// assert_eq!(passphrase.split(' ').count(), 4);
+    /// assert!(passphrase.split(' ').all(|w| w.chars().next().unwrap().is_uppercase()));
+    /// assert_eq!(policy.entropy(), 4.0 * 7776_f64.log2());
+    /// ```
+    pub fn generate(&self) -> Result<String, String> {
+        if self.words == 0 {
+            return Err(String::from("A passphrase needs at least one word"));
+        }
+        let wordlist = self.wordlist.words();
+        let mut rng = StdRng::from_os_rng();
+        let mut words: Vec<String> = (0..self.words)
+            .map(|_| {
+                let word = wordlist[rng.random_range(..wordlist.len())];
+                match self.capitalization {
+                    Capitalization::Lower => word.to_lowercase(),
+                    Capitalization::Upper => word.to_uppercase(),
+                    Capitalization::Title => title_case(word),
+                    Capitalization::Random if rng.random_bool(0.5) => title_case(word),
+                    Capitalization::Random => word.to_lowercase(),
+                }
+            })
+            .collect();
+        let digits: Vec<char> = DIGITS.chars().collect();
+        let symbols: Vec<char> = SYMBOLS.chars().collect();
+        for (enabled, characters) in [(self.digit, digits), (self.symbol, symbols)] {
+            if enabled {
+                let index = rng.random_range(..words.len());
+                words[index].push(characters[rng.random_range(..characters.len())]);
+            }
+        }
+        Ok(words.join(&self.separator))
+    }
+    /// words * log2(wordlist size) plus whatever capitalization and the extra characters add
+    pub fn entropy(&self) -> f64 {
+        let words = self.words as f64;
+        let mut entropy = words * (self.wordlist.words().len() as f64).log2();
+        if self.capitalization == Capitalization::Random {
+            entropy += words;
+        }
+        if self.digit {
+            entropy += (DIGITS.len() as f64).log2() + words.log2();
+        }
+        if self.symbol {
+            entropy += (SYMBOLS.len() as f64).log2() + words.log2();
+        }
+        entropy
+    }
+}
+
+fn title_case(word: &str) -> String {
+    let mut characters = word.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
+
+/// A template where some characters stand for a character class:
+///
+/// c: lowercase consonant, C: uppercase consonant  
+/// v: lowercase vowel, V: uppercase vowel  
+/// a: lowercase letter, A: uppercase letter  
+/// 9: digit  
+/// !: symbol  
+/// \x: x as is  
+///
+/// everything else is kept as is (so `Cvccvc-99!` gives something like `Dalken-42$`)
+#[derive(Debug, Clone)]
+pub struct PatternPolicy {
+    pub template: String,
+}
+
+/// A part of a parsed [PatternPolicy] template
+enum PatternPart {
+    Class(Vec<char>),
+    Literal(char),
+}
+
+impl PatternPolicy {
+    fn parts(&self) -> Vec<PatternPart> {
+        let mut parts = vec![];
+        let mut characters = self.template.chars();
+        while let Some(character) = characters.next() {
+            let class: String = match character {
+                'c' => CONSONANTS.to_string(),
+                'C' => CONSONANTS.to_uppercase(),
+                'v' => VOWELS.to_string(),
+                'V' => VOWELS.to_uppercase(),
+                'a' => LOWERCASE.to_string(),
+                'A' => UPPERCASE.to_string(),
+                '9' => DIGITS.to_string(),
+                '!' => SYMBOLS.to_string(),
+                '\\' => {
+                    if let Some(escaped) = characters.next() {
+                        parts.push(PatternPart::Literal(escaped));
+                    }
+                    continue;
+                }
+                literal => {
+                    parts.push(PatternPart::Literal(literal));
+                    continue;
+                }
+            };
+            parts.push(PatternPart::Class(class.chars().collect()));
+        }
+        parts
+    }
+    /// Fills in the template
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::generator::PatternPolicy;
+    /// let policy = PatternPolicy { template: String::from("Cvccvc-99\\!") };
+    /// let password = policy.generate().unwrap();
+    /// assert_eq!(password.len(), 10);
+    /// assert!(password.chars().next().unwrap().is_uppercase());
+    /// assert!(password.ends_with('!'));
+    /// ```
+    pub fn generate(&self) -> Result<String, String> {
+        let parts = self.parts();
+        if !parts.iter().any(|p| matches!(p, PatternPart::Class(_))) {
+            return Err(String::from(
+                "The pattern doesn't have any random characters",
+            ));
+        }
+        let mut rng = StdRng::from_os_rng();
+        Ok(parts
+            .iter()
+            .map(|part| match part {
+                PatternPart::Class(class) => class[rng.random_range(..class.len())],
+                PatternPart::Literal(literal) => *literal,
+            })
+            .collect())
+    }
+    /// The sum of log2(class size) of every random character
+    pub fn entropy(&self) -> f64 {
+        self.parts()
+            .iter()
+            .map(|part| match part {
+                PatternPart::Class(class) => (class.len() as f64).log2(),
+                PatternPart::Literal(_) => 0.0,
+            })
+            .sum()
+    }
+}
+
+/// Made of syllables so it can be read out loud, like `bakor-shitel-07`
+#[derive(Debug, Clone)]
+pub struct PronounceablePolicy {
+    pub syllables: usize,
+    /// Digits added to the end
+    pub digits: usize,
+    /// Makes the first letter uppercase
+    pub capitalize: bool,
+}
+
+impl Default for PronounceablePolicy {
+    fn default() -> Self {
+        Self {
+            syllables: 6,
+            digits: 2,
+            capitalize: false,
+        }
+    }
+}
+
+impl PronounceablePolicy {
+    /// Every two syllables make a word and words are separated by '-'
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::generator::PronounceablePolicy;
+    /// let policy = PronounceablePolicy { syllables: 4, digits: 2, capitalize: true };
+    /// let password = policy.generate().unwrap();
+    /// assert_eq!(password.split('-').count(), 3);
+    /// assert!(password.chars().next().unwrap().is_uppercase());
+    /// ```
+    pub fn generate(&self) -> Result<String, String> {
+        if self.syllables == 0 {
+            return Err(String::from(
+                "A pronounceable password needs at least one syllable",
+            ));
+        }
+        let mut rng = StdRng::from_os_rng();
+        let vowels: Vec<char> = VOWELS.chars().collect();
+        let syllables: Vec<String> = (0..self.syllables)
+            .map(|_| {
+                format!(
+                    "{}{}{}",
+                    ONSETS[rng.random_range(..ONSETS.len())],
+                    vowels[rng.random_range(..vowels.len())],
+                    CODAS[rng.random_range(..CODAS.len())]
+                )
+            })
+            .collect();
+        let mut words: Vec<String> = syllables.chunks(2).map(|c| c.concat()).collect();
+        if self.digits > 0 {
+            let digits: String = (0..self.digits)
+                .map(|_| char::from(b'0' + rng.random_range(0..10)))
+                .collect();
+            words.push(digits);
+        }
+        let password = words.join("-");
+        if self.capitalize {
+            return Ok(title_case(&password));
+        }
+        Ok(password)
+    }
+    /// Counts every syllable as one of onsets * vowels * codas
+    pub fn entropy(&self) -> f64 {
+        let syllable = (ONSETS.len() * VOWELS.len() * CODAS.len()) as f64;
+        self.syllables as f64 * syllable.log2() + self.digits as f64 * 10_f64.log2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_policy_minimums_and_exclusions() {
+        let policy = PasswordPolicy {
+            length: 20,
+            classes: vec![(CharClass::Digits, 5), (CharClass::Lowercase, 1)],
+            exclude: String::from("abc"),
+            no_look_alikes: true,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let password = policy.generate().unwrap();
+            assert_eq!(password.len(), 20);
+            assert!(password.chars().filter(|c| c.is_ascii_digit()).count() >= 5);
+            assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+            assert!(!password.contains(['0', '1', 'l', 'a', 'b', 'c']));
+            assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+        }
+    }
+
+    #[test]
+    fn password_policy_no_repeats_or_sequences() {
+        let policy = PasswordPolicy {
+            length: 30,
+            classes: vec![(CharClass::Digits, 0)],
+            no_repeats: true,
+            no_sequences: true,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let password: Vec<char> = policy.generate().unwrap().chars().collect();
+            assert!(password.windows(2).all(|w| w[0] != w[1]));
+            assert!(password.windows(3).all(|w| {
+                let (a, b, c) = (w[0] as i32, w[1] as i32, w[2] as i32);
+                !(b - a == c - b && (b - a).abs() == 1)
+            }));
+        }
+    }
+
+    #[test]
+    fn password_policy_errors() {
+        let excluded = PasswordPolicy {
+            classes: vec![(CharClass::Digits, 1)],
+            exclude: String::from(DIGITS),
+            ..Default::default()
+        };
+        assert!(excluded.generate().is_err());
+        let too_short = PasswordPolicy {
+            length: 3,
+            classes: vec![(CharClass::Digits, 2), (CharClass::Lowercase, 2)],
+            ..Default::default()
+        };
+        assert!(too_short.generate().is_err());
+        let nothing = PasswordPolicy {
+            classes: vec![],
+            ..Default::default()
+        };
+        assert!(nothing.generate().is_err());
+    }
+
+    #[test]
+    fn password_policy_entropy() {
+        let nothing = PasswordPolicy {
+            classes: vec![],
+            ..Default::default()
+        };
+        assert_eq!(nothing.entropy(), 0.0);
+        let policy = PasswordPolicy {
+            length: 10,
+            classes: vec![(CharClass::Digits, 1)],
+            ..Default::default()
+        };
+        assert!((policy.entropy() - 10.0 * 10_f64.log2()).abs() < 1e-9);
+        let policy = PasswordPolicy {
+            length: 16,
+            classes: vec![(CharClass::Lowercase, 1), (CharClass::Uppercase, 1)],
+            exclude: String::from("abcdefghijklABCDEFGHIJKL"),
+            ..Default::default()
+        };
+        // 14 + 14 letters are left
+        assert!((policy.entropy() - 16.0 * 28_f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wordlists() {
+        let large = Wordlist::Large.words();
+        assert_eq!(large.len(), 7776);
+        assert_eq!(large.iter().collect::<HashSet<_>>().len(), large.len());
+        assert_eq!((large[0], large[7775]), ("abacus", "zoom"));
+        let short = Wordlist::Short.words();
+        assert_eq!(short.len(), 1296);
+        assert_eq!(short.iter().collect::<HashSet<_>>().len(), short.len());
+        assert!(short.iter().all(|word| word.len() <= 5));
+        assert!(Wordlist::parse("large").is_ok());
+        assert!(Wordlist::parse("/nonexistent/wordlist").is_err());
+    }
+
+    #[test]
+    fn custom_wordlist_uses_last_word_of_a_line() {
+        let path = std::env::temp_dir().join(format!("nspm_wordlist_{}", std::process::id()));
+        std::fs::write(&path, "11111\tabacus\n11112\tabdomen\n11113 abacus\n\n").unwrap();
+        let wordlist = Wordlist::parse(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(wordlist.unwrap().words(), ["abacus", "abdomen"]);
+    }
+
+    #[test]
+    fn passphrase() {
+        let policy = PassphrasePolicy {
+            words: 4,
+            separator: String::from(" "),
+            capitalization: Capitalization::Title,
+            ..Default::default()
+        };
+        let passphrase = policy.generate().unwrap();
+        assert_eq!(passphrase.split(' ').count(), 4);
+        assert!(
+            passphrase
+                .split(' ')
+                .all(|w| w.chars().next().unwrap().is_uppercase())
+        );
+        assert_eq!(policy.entropy(), 4.0 * 7776_f64.log2());
+
+        let policy = PassphrasePolicy {
+            words: 3,
+            digit: true,
+            symbol: true,
+            capitalization: Capitalization::Upper,
+            separator: String::from(" "),
+            ..Default::default()
+        };
+        let passphrase = policy.generate().unwrap();
+        assert_eq!(passphrase.split(' ').count(), 3);
+        assert_eq!(passphrase.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+        assert!(!passphrase.chars().any(|c| c.is_lowercase()));
+        let expected = 3.0 * 7776_f64.log2()
+            + 10_f64.log2()
+            + (SYMBOLS.len() as f64).log2()
+            + 2.0 * 3_f64.log2();
+        assert!((policy.entropy() - expected).abs() < 1e-9);
+
+        let zero = PassphrasePolicy {
+            words: 0,
+            ..Default::default()
+        };
+        assert!(zero.generate().is_err());
+    }
+
+    #[test]
+    fn capitalization() {
+        assert_eq!(Capitalization::parse(" Title "), Ok(Capitalization::Title));
+        assert!(Capitalization::parse("camel").is_err());
+        assert_eq!(title_case("word"), "Word");
+        assert_eq!(title_case(""), "");
+    }
+
+    #[test]
+    fn pattern() {
+        let policy = PatternPolicy {
+            template: String::from("Cvccvc-99\\!"),
+        };
+        let password: Vec<char> = policy.generate().unwrap().chars().collect();
+        assert_eq!(password.len(), 10);
+        assert!(CONSONANTS.to_uppercase().contains(password[0]));
+        assert!(VOWELS.contains(password[1]));
+        assert_eq!(password[6], '-');
+        assert!(password[7].is_ascii_digit() && password[8].is_ascii_digit());
+        assert_eq!(password[9], '!');
+        let expected = 4.0 * 21_f64.log2() + 2.0 * 5_f64.log2() + 2.0 * 10_f64.log2();
+        assert!((policy.entropy() - expected).abs() < 1e-9);
+
+        let literal = PatternPolicy {
+            template: String::from("-\\c-"),
+        };
+        assert!(literal.generate().is_err());
+        assert_eq!(literal.entropy(), 0.0);
+    }
+
+    #[test]
+    fn pronounceable() {
+        let policy = PronounceablePolicy {
+            syllables: 4,
+            digits: 2,
+            capitalize: true,
+        };
+        let password = policy.generate().unwrap();
+        let parts: Vec<&str> = password.split('-').collect();
+        assert_eq!(parts.len(), 3);
+        assert!(password.chars().next().unwrap().is_uppercase());
+        assert!(parts[2].len() == 2 && parts[2].chars().all(|c| c.is_ascii_digit()));
+
+        let odd = PronounceablePolicy {
+            syllables: 3,
+            digits: 0,
+            capitalize: false,
+        };
+        assert_eq!(odd.generate().unwrap().split('-').count(), 2);
+        assert!(
+            PronounceablePolicy {
+                syllables: 0,
+                ..Default::default()
+            }
+            .generate()
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Undo and redo of the changes to the entries of a [PasswordArray][crate::storage::PasswordArray]
+
+use crate::storage::Password;
+
+/// One change to the list of entries
+#[derive(Debug, Clone)]
+pub enum Mutation {
+    /// An entry was put at this index
+    Insert(usize, Password),
+    /// The entry at this index was taken out
+    Remove(usize, Password),
+    /// The entry at this index changed from the first [Password] to the second one
+    Replace(usize, Password, Password),
+}
+
+impl Mutation {
+    pub fn apply(&self, entries: &mut Vec<Password>) {
+        match self {
+            Mutation::Insert(index, password) => entries.insert(*index, password.clone()),
+            Mutation::Remove(index, _) => {
+                entries.remove(*index);
+            }
+            Mutation::Replace(index, _, after) => entries[*index] = after.clone(),
+        }
+    }
+    /// The mutation that undoes this one
+    fn inverse(&self) -> Mutation {
+        match self {
+            Mutation::Insert(index, password) => Mutation::Remove(*index, password.clone()),
+            Mutation::Remove(index, password) => Mutation::Insert(*index, password.clone()),
+            Mutation::Replace(index, before, after) => {
+                Mutation::Replace(*index, after.clone(), before.clone())
+            }
+        }
+    }
+    fn passwords_mut(&mut self) -> Vec<&mut Password> {
+        match self {
+            Mutation::Insert(_, password) | Mutation::Remove(_, password) => vec![password],
+            Mutation::Replace(_, before, after) => vec![before, after],
+        }
+    }
+}
+
+/// The mutations of one action (like "Add github"), they're undone and redone together
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub description: String,
+    mutations: Vec<Mutation>,
+}
+
+/// The commands done since the vault was loaded, the ones after `position` were undone and can be
+/// redone until something else is done
+#[derive(Debug, Clone)]
+pub struct History {
+    commands: Vec<Command>,
+    position: usize,
+    /// The position the vault was last saved at, None when that was undone and then replaced
+    saved: Option<usize>,
+    /// A command that's being put together with [begin][History::begin]
+    group: Option<Command>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            commands: vec![],
+            position: 0,
+            saved: Some(0),
+            group: None,
+        }
+    }
+}
+
+impl History {
+    /// Applies `mutation` to `entries` and remembers it as a command (or as a part of the group
+    /// from [begin][History::begin])
+    pub fn execute(
+        &mut self,
+        description: String,
+        mutation: Mutation,
+        entries: &mut Vec<Password>,
+    ) {
+        mutation.apply(entries);
+        if let Some(group) = &mut self.group {
+            group.mutations.push(mutation);
+            return;
+        }
+        self.push(Command {
+            description,
+            mutations: vec![mutation],
+        });
+    }
+    /// Makes everything executed until [end][History::end] one command
+    pub fn begin(&mut self, description: String) {
+        self.group = Some(Command {
+            description,
+            mutations: vec![],
+        });
+    }
+    pub fn end(&mut self) {
+        if let Some(group) = self.group.take()
+            && !group.mutations.is_empty()
+        {
+            self.push(group);
+        }
+    }
+    fn push(&mut self, command: Command) {
+        self.commands.truncate(self.position);
+        if self.saved.is_some_and(|saved| saved > self.position) {
+            self.saved = None;
+        }
+        self.commands.push(command);
+        self.position += 1;
+    }
+    /// Undoes the last command, returns its description and the mutations that were applied to
+    /// undo it (None if there's nothing to undo)
+    pub fn undo(&mut self, entries: &mut Vec<Password>) -> Option<(&str, Vec<Mutation>)> {
+        self.position = self.position.checked_sub(1)?;
+        let command = &self.commands[self.position];
+        let inverses: Vec<Mutation> = command
+            .mutations
+            .iter()
+            .rev()
+            .map(Mutation::inverse)
+            .collect();
+        for mutation in inverses.iter() {
+            mutation.apply(entries);
+        }
+        Some((&command.description, inverses))
+    }
+    /// Does the last undone command again, returns its description and its mutations (None if
+    /// there's nothing to redo)
+    pub fn redo(&mut self, entries: &mut Vec<Password>) -> Option<(&str, Vec<Mutation>)> {
+        let command = self.commands.get(self.position)?;
+        for mutation in command.mutations.iter() {
+            mutation.apply(entries);
+        }
+        self.position += 1;
+        Some((&command.description, command.mutations.clone()))
+    }
+    /// Remembers that the vault was saved at the current position
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.position);
+    }
+    /// If the entries are the same as when the vault was last saved (or loaded)
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.position)
+    }
+    /// Every [Password] kept for undoing and redoing (to lock them together with the vault)
+    pub fn passwords_mut(&mut self) -> impl Iterator<Item = &mut Password> {
+        self.commands
+            .iter_mut()
+            .flat_map(|command| command.mutations.iter_mut())
+            .flat_map(|mutation| mutation.passwords_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cryptography::KdfParams;
+    use secrecy::SecretString;
+
+    fn entry(service: &str) -> Password {
+        Password::new(
+            service.to_string(),
+            SecretString::from("p"),
+            SecretString::from("master"),
+            KdfParams::default(),
+        )
+    }
+
+    /// Entries have random salts, so equal debug output means the same entries
+    fn assert_entries(entries: &[Password], expected: &[&Password]) {
+        assert_eq!(format!("{entries:?}"), format!("{expected:?}"));
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let (github, gitlab) = (entry("github"), entry("gitlab"));
+        let mut history = History::default();
+        let mut entries = vec![];
+        history.execute(
+            "Add github".into(),
+            Mutation::Insert(0, github.clone()),
+            &mut entries,
+        );
+        history.execute(
+            "Change github".into(),
+            Mutation::Replace(0, github.clone(), gitlab.clone()),
+            &mut entries,
+        );
+        assert_entries(&entries, &[&gitlab]);
+
+        assert_eq!(history.undo(&mut entries).unwrap().0, "Change github");
+        assert_entries(&entries, &[&github]);
+        assert_eq!(history.undo(&mut entries).unwrap().0, "Add github");
+        assert_entries(&entries, &[]);
+        assert!(history.undo(&mut entries).is_none());
+
+        assert_eq!(history.redo(&mut entries).unwrap().0, "Add github");
+        assert_eq!(history.redo(&mut entries).unwrap().0, "Change github");
+        assert_entries(&entries, &[&gitlab]);
+        assert!(history.redo(&mut entries).is_none());
+    }
+
+    #[test]
+    fn groups_are_undone_together() {
+        let (github, gitlab) = (entry("github"), entry("gitlab"));
+        let mut history = History::default();
+        let mut entries = vec![];
+        history.begin("Import".into());
+        history.execute(
+            String::new(),
+            Mutation::Insert(0, github.clone()),
+            &mut entries,
+        );
+        history.execute(
+            String::new(),
+            Mutation::Insert(1, gitlab.clone()),
+            &mut entries,
+        );
+        history.end();
+        history.execute(
+            "Remove github".into(),
+            Mutation::Remove(0, github),
+            &mut entries,
+        );
+        assert_entries(&entries, &[&gitlab]);
+
+        history.undo(&mut entries);
+        assert_eq!(history.undo(&mut entries).unwrap().0, "Import");
+        assert_entries(&entries, &[]);
+    }
+
+    #[test]
+    fn new_mutation_clears_redo() {
+        let (github, gitlab) = (entry("github"), entry("gitlab"));
+        let mut history = History::default();
+        let mut entries = vec![];
+        history.execute(
+            "Add github".into(),
+            Mutation::Insert(0, github),
+            &mut entries,
+        );
+        history.undo(&mut entries);
+        history.execute(
+            "Add gitlab".into(),
+            Mutation::Insert(0, gitlab.clone()),
+            &mut entries,
+        );
+        assert!(history.redo(&mut entries).is_none());
+        assert_entries(&entries, &[&gitlab]);
+    }
+
+    #[test]
+    fn saved_marker() {
+        let mut history = History::default();
+        let mut entries = vec![];
+        assert!(history.is_saved());
+        history.execute(
+            "Add github".into(),
+            Mutation::Insert(0, entry("github")),
+            &mut entries,
+        );
+        assert!(!history.is_saved());
+        history.undo(&mut entries);
+        assert!(history.is_saved());
+        history.redo(&mut entries);
+        history.mark_saved();
+        assert!(history.is_saved());
+
+        // the saved position can't be reached again once it's undone and replaced
+        history.undo(&mut entries);
+        assert!(!history.is_saved());
+        history.execute(
+            "Add gitlab".into(),
+            Mutation::Insert(0, entry("gitlab")),
+            &mut entries,
+        );
+        history.undo(&mut entries);
+        assert!(!history.is_saved());
+    }
+}
@@ -0,0 +1,268 @@
+//! An encrypted file next to a vault (`<vault>_journal`) that every change is appended to before
+//! it's saved, so the changes can be replayed when nspm didn't get to save them (it crashed, the
+//! terminal was closed, ...)
+//!
+//! The first line is a salt, every other line is a JSON record encrypted with a key derived from
+//! the master password and that salt
+
+use crate::cryptography::{
+    KdfParams, decrypt_with_key, derive_key, encrypt_with_key, generate_salt,
+};
+use argon2::password_hash::SaltString;
+use rand_core::OsRng;
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::Value;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+pub struct Journal {
+    path: String,
+    /// Derived once, it's forgotten when the vault is locked
+    key: Option<SecretString>,
+}
+
+impl Journal {
+    /// The journal of the vault in `directory` (the file doesn't have to exist yet)
+    pub fn new(directory: &str) -> Journal {
+        Journal {
+            path: format!("{directory}_journal"),
+            key: None,
+        }
+    }
+    pub fn exists(&self) -> bool {
+        fs::exists(&self.path).unwrap_or(false)
+    }
+    /// Encrypts `record` and adds it to the end of the journal (which is made if it doesn't exist)
+    pub fn append(
+        &mut self,
+        record: &Value,
+        master_password: &SecretString,
+        kdf: &KdfParams,
+    ) -> Result<(), String> {
+        if !self.exists() {
+            let salt = generate_salt(&mut OsRng).map_err(|error| error.to_string())?;
+            fs::write(&self.path, format!("{}\n", salt.as_str()))
+                .map_err(|error| format!("Error when making the journal: {error}"))?;
+            self.key = Some(derive_key(
+                master_password.expose_secret().as_bytes(),
+                &salt,
+                kdf,
+            ));
+        }
+        let key = self.key(master_password, kdf)?;
+        let line = encrypt_with_key(record.to_string().as_bytes(), &key);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|error| format!("Error when opening the journal: {error}"))?;
+        writeln!(file, "{line}")
+            .and_then(|_| file.sync_data())
+            .map_err(|error| format!("Error when writing to the journal: {error}"))
+    }
+    /// Decrypts every record in the journal
+    pub fn read(
+        &mut self,
+        master_password: &SecretString,
+        kdf: &KdfParams,
+    ) -> Result<Vec<Value>, String> {
+        let key = self.key(master_password, kdf)?;
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|error| format!("Error when reading the journal: {error}"))?;
+        let lines: Vec<&str> = contents.lines().skip(1).collect();
+        let mut records = vec![];
+        for (index, line) in lines.iter().enumerate() {
+            let record = decrypt_with_key(line, &key).and_then(|record| {
+                serde_json::from_str(record.expose_secret()).map_err(|error| error.to_string())
+            });
+            match record {
+                Ok(record) => records.push(record),
+                // nspm stopped in the middle of writing it
+                Err(_) if index + 1 == lines.len() => {}
+                Err(error) => return Err(format!("The journal can't be read: {error}")),
+            }
+        }
+        Ok(records)
+    }
+    /// Deletes the journal (after saving, or when the changes in it are thrown away)
+    pub fn remove(&mut self) -> Result<(), String> {
+        self.key = None;
+        if !self.exists() {
+            return Ok(());
+        }
+        fs::remove_file(&self.path)
+            .map_err(|error| format!("Error when removing the journal: {error}"))
+    }
+    /// Throws away the derived key, it's derived again on the next [append][Journal::append] or
+    /// [read][Journal::read]
+    pub fn forget_key(&mut self) {
+        self.key = None;
+    }
+    fn key(
+        &mut self,
+        master_password: &SecretString,
+        kdf: &KdfParams,
+    ) -> Result<SecretString, String> {
+        if let Some(key) = &self.key {
+            return Ok(key.clone());
+        }
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|error| format!("Error when reading the journal: {error}"))?;
+        let salt = contents
+            .lines()
+            .next()
+            .and_then(|salt| SaltString::from_b64(salt).ok())
+            .ok_or("The journal doesn't start with a salt")?;
+        let key = derive_key(master_password.expose_secret().as_bytes(), &salt, kdf);
+        self.key = Some(key.clone());
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{PasswordArray, VaultSettings, initialize_directory};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("nspm_{name}_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+        fn join(&self, name: impl AsRef<str>) -> String {
+            self.0.join(name.as_ref()).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn kdf() -> KdfParams {
+        KdfParams {
+            memory_cost: 64,
+            time_cost: 1,
+            ..KdfParams::default()
+        }
+    }
+
+    /// Makes an empty vault in `temp_dir` and loads it with the journal enabled
+    fn vault(temp_dir: &TempDir) -> PasswordArray {
+        let directory = temp_dir.join("vault");
+        if !fs::exists(&directory).unwrap() {
+            let settings = VaultSettings {
+                kdf: kdf(),
+                ..VaultSettings::default()
+            };
+            initialize_directory(&directory, "master", &settings).unwrap();
+        }
+        let mut password_array = PasswordArray::new(SecretString::from("master"), directory);
+        password_array.load(false).unwrap();
+        password_array.enable_journal();
+        password_array
+    }
+
+    #[test]
+    fn changes_are_replayed() {
+        let temp_dir = TempDir::new("journal_replay");
+        let mut password_array = vault(&temp_dir);
+        password_array
+            .add_password("github".into(), SecretString::from("a"))
+            .unwrap();
+        password_array
+            .add_password("gitlab".into(), SecretString::from("b"))
+            .unwrap();
+        password_array.remove_password("github".into()).unwrap();
+        // nspm stops without saving
+        drop(password_array);
+
+        let mut password_array = vault(&temp_dir);
+        assert!(password_array.has_journal());
+        password_array.replay_journal().unwrap();
+        assert_eq!(password_array.get_services(), ["gitlab"]);
+        assert_eq!(
+            password_array
+                .get_password("gitlab")
+                .unwrap()
+                .expose_secret(),
+            "b"
+        );
+        assert!(password_array.has_unsaved_changes());
+        // the replayed changes are undone together
+        password_array.undo().unwrap();
+        assert!(password_array.get_services().is_empty());
+    }
+
+    #[test]
+    fn torn_last_record_is_ignored() {
+        let temp_dir = TempDir::new("journal_torn");
+        let master_password = SecretString::from("master");
+        let mut journal = Journal::new(&temp_dir.join("vault"));
+        journal.append(&json!(1), &master_password, &kdf()).unwrap();
+        journal.append(&json!(2), &master_password, &kdf()).unwrap();
+        let contents = fs::read_to_string(&journal.path).unwrap();
+        let torn = &contents[..contents.len() - 10];
+        fs::write(&journal.path, torn).unwrap();
+
+        let mut journal = Journal::new(&temp_dir.join("vault"));
+        assert_eq!(journal.read(&master_password, &kdf()).unwrap(), [json!(1)]);
+
+        // a broken record before the last one means the journal is corrupt
+        fs::write(&journal.path, format!("{torn}\n")).unwrap();
+        journal.append(&json!(3), &master_password, &kdf()).unwrap();
+        assert!(journal.read(&master_password, &kdf()).is_err());
+    }
+
+    #[test]
+    fn journal_without_salt_is_an_error() {
+        let temp_dir = TempDir::new("journal_no_salt");
+        let mut journal = Journal::new(&temp_dir.join("vault"));
+        fs::write(&journal.path, "not a salt\n").unwrap();
+        assert!(journal.read(&SecretString::from("master"), &kdf()).is_err());
+    }
+
+    #[test]
+    fn journal_of_another_version_is_not_replayed() {
+        let temp_dir = TempDir::new("journal_header");
+        let mut password_array = vault(&temp_dir);
+        password_array
+            .add_password("github".into(), SecretString::from("a"))
+            .unwrap();
+        drop(password_array);
+
+        // saved by a session without the journal, so the entries changed since it was written
+        let mut other = PasswordArray::new(SecretString::from("master"), temp_dir.join("vault"));
+        other.load(false).unwrap();
+        other
+            .add_password("gitlab".into(), SecretString::from("b"))
+            .unwrap();
+        other.save(false).unwrap();
+
+        let mut password_array = vault(&temp_dir);
+        assert!(password_array.has_journal());
+        assert!(password_array.replay_journal().is_err());
+        assert_eq!(password_array.get_services(), ["gitlab"]);
+    }
+
+    #[test]
+    fn journal_is_removed_after_save() {
+        let temp_dir = TempDir::new("journal_save");
+        let mut password_array = vault(&temp_dir);
+        password_array
+            .add_password("github".into(), SecretString::from("a"))
+            .unwrap();
+        assert!(password_array.has_journal());
+        password_array.save(false).unwrap();
+        assert!(!password_array.has_journal());
+        assert!(!fs::exists(temp_dir.join("vault_journal")).unwrap());
+    }
+}
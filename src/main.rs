@@ -2,27 +2,72 @@
 //! so don't set your expectations too high
 
 pub mod ansi;
+pub mod audit;
+pub mod breach;
+pub mod config;
 pub mod cryptography;
+pub mod exitcode;
+pub mod fuzzy;
+pub mod generator;
+pub mod history;
+pub mod journal;
+pub mod otp;
+pub mod passwordrules;
+pub mod passwordsource;
+pub mod rotation;
 pub mod storage;
+pub mod strength;
+pub mod tui;
 pub mod ui;
 
 use ansi::constants::*;
-use clap::Parser;
-use secrecy::SecretString;
-use std::process::exit;
-use storage::{PasswordArray, verify_directory};
+use audit::{AuditReport, DEFAULT_MAX_AGE_DAYS};
+use breach::BreachDatabase;
+use clap::{Parser, Subcommand};
+use config::Config;
+use cryptography::{Cipher, Kdf};
+use generator::{
+    Capitalization, CharClass, Generator, PassphrasePolicy, PasswordPolicy, PatternPolicy,
+    PronounceablePolicy, Wordlist,
+};
+use getch_rs::Key;
+use otp::Otp;
+use passwordrules::PasswordRules;
+use passwordsource::PasswordSource;
+use rotation::Expiry;
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::{Value, json};
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    process::exit,
+};
+use storage::{
+    FORMAT_VERSION, PasswordArray, VaultInfo, VaultSettings, check_readable, initialize_directory,
+    verify_directory, verify_vault,
+};
+use tui::Tui;
 use ui::{
-    ALL_FLAGS, InputFlags, Menu, MenuConfig, NO_COMMANDS, NO_FLAGS, YESES, directory_selector,
-    generate_password, input, new_password_input, pause, prompt_master_password, prompt_number,
+    ALL_FLAGS, DEFAULT_CLIP_TIMEOUT, DEFAULT_LOCK_TIMEOUT, FuzzyPicker, InputFlags, Locked, Menu,
+    MenuConfig, MultiSelect, NO_COMMANDS, NO_FLAGS, PasswordList, YESES, clear_clipboard_after,
+    confirm, copy_to_clipboard, details, directory_selector, enter_alternate_screen,
+    generate_password, input, leave_alternate_screen, live_view, lock_vault, master_password,
+    new_password_input, password_input, pause, prompt_generator, prompt_master_password,
+    prompt_number, quit, set_lock_timeout, without_lock,
 };
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[arg(short, long, default_value_t = String::from("\0\0\0\0"))]
-    /// The directory nspm uses, if not specified it'll bring up the directory selector
+    #[arg(short, long, global = true, default_value_t = String::from("\0\0\0\0"))]
+    /// The directory nspm uses, if not specified it's the directory in the config or the
+    /// directory selector comes up
     directory: String,
 
+    #[arg(long, global = true)]
+    /// The config file (default: ~/.config/nspm/config)
+    config: Option<String>,
+
     #[arg(short, long, default_value_t = String::from("nspm v1.0.1"))]
     /// Menu prompt
     prompt: String,
@@ -49,11 +94,461 @@ struct Args {
     /// short_path: %s  
     /// absolute_path: %S  
     format_string: String,
+
+    #[arg(long, global = true)]
+    /// A downloaded Have I Been Pwned password dump (a sorted SHA-1 file or a directory of range
+    /// files), new passwords get checked against it
+    breach_db: Option<String>,
+
+    #[arg(long, global = true, value_name = "SECONDS")]
+    /// Seconds until a copied password is cleared from the clipboard, 0 never clears it (default:
+    /// 30)
+    clip_timeout: Option<u64>,
+
+    #[arg(long, global = true, value_name = "MINUTES")]
+    /// Minutes without a key until the menu or TUI locks the vault (the master password has to be
+    /// typed in again, unsaved changes are kept), 0 never locks it (default: 5)
+    lock_timeout: Option<u64>,
+
+    #[arg(long, global = true)]
+    /// Show every password at once in "List passwords" and nspm ls (as service<TAB>password
+    /// lines, or in the password fields with --format json), anyone who can see the screen can
+    /// read them
+    reveal_all: bool,
+
+    #[arg(long, global = true, default_value = "table", value_parser = ["table", "json"])]
+    /// table or json (for ls, show, audit and info, the JSON schemas are in the README)
+    format: String,
+
+    #[arg(long, global = true, group = "password_source", value_name = "N")]
+    /// Read the master password from this open file descriptor (like --password-fd 3 with
+    /// 3< <(pass show nspm)), it doesn't touch the disk, the environment or the command line
+    password_fd: Option<u32>,
+
+    #[arg(long, global = true, group = "password_source", value_name = "PATH")]
+    /// Read the master password from the first line of this file, anyone (and any backup) that can
+    /// read the file can read the password so keep it chmod 600
+    password_file: Option<String>,
+
+    #[arg(long, global = true, group = "password_source", value_name = "VAR")]
+    /// Read the master password from this environment variable, processes started by nspm inherit
+    /// it and other processes of the same user can read it
+    password_env: Option<String>,
+
+    #[arg(long, global = true, group = "password_source", value_name = "CMD")]
+    /// Use the first line this shell command prints as the master password, it runs with your
+    /// privileges and shows up in ps
+    password_command: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Adds a service, the password is read from stdin when it's piped (needs --directory)
+    Add {
+        service: String,
+
+        #[arg(long)]
+        /// Generate the password and print it
+        generate: bool,
+
+        #[arg(long)]
+        /// Tags of the service (can be used more than once)
+        tag: Vec<String>,
+    },
+    /// Prints the password of a service (needs --directory)
+    Get {
+        service: String,
+
+        #[arg(long)]
+        /// Copy it to the clipboard (with OSC 52) instead of printing it
+        clip: bool,
+    },
+    /// Changes the password of a service, the password is read from stdin when it's piped (needs
+    /// --directory)
+    Edit {
+        service: String,
+
+        #[arg(long)]
+        /// Generate the password (under the passwordrules of the service) and print it
+        generate: bool,
+    },
+    /// Removes a service (needs --directory)
+    Rm { service: String },
+    /// Renames a service (needs --directory)
+    Mv { service: String, new_name: String },
+    /// Lists the services (needs --directory)
+    Ls {
+        #[arg(long)]
+        /// Only the services with this tag
+        tag: Option<String>,
+    },
+    /// Searches for a service as you type and prints the one that was chosen (needs --directory)
+    Pick {
+        /// What to start searching for
+        #[arg(default_value_t = String::new())]
+        query: String,
+    },
+    /// Shows the details of a service (needs --directory)
+    Show {
+        service: String,
+
+        #[arg(long)]
+        /// Show the password too
+        reveal: bool,
+    },
+    /// Prints the current 2FA code of a service (needs --directory)
+    Otp {
+        /// The service the OTP secret is stored on
+        service: String,
+
+        #[arg(long)]
+        /// Copy it to the clipboard (with OSC 52) instead of printing it
+        clip: bool,
+    },
+    /// Prints a generated password (and its entropy to stderr)
+    Generate(Box<GenerateArgs>),
+    /// Imports 2FA seeds from a file with one otpauth:// or otpauth-migration:// URI per line
+    /// (needs --directory)
+    ImportOtp {
+        /// The file with the URIs
+        file: String,
+    },
+    /// Generates new passwords for many services and marks them done one by one as the services
+    /// accept them, saving after every one (needs --directory)
+    Rotate {
+        #[arg(long)]
+        /// Rotate every service with this tag
+        tag: Option<String>,
+
+        #[arg(long)]
+        /// Only the services that still have a pending password (to finish an earlier rotation)
+        pending: bool,
+
+        /// Services to rotate, without them (and --tag) the services can be picked
+        services: Vec<String>,
+    },
+    /// Reports reused, weak, old, near-duplicate and breached passwords (needs --directory)
+    Audit {
+        #[arg(long, default_value_t = DEFAULT_MAX_AGE_DAYS)]
+        /// Passwords not changed in this many days are reported as old
+        max_age: u64,
+    },
+    /// Makes a new vault, the master password is read from stdin when it's piped
+    Init {
+        /// Where the vault is made
+        path: String,
+
+        #[command(flatten, next_help_heading = "KDF options")]
+        kdf: KdfArgs,
+    },
+    /// Shows the format, KDF parameters and size of a vault without unlocking it (needs
+    /// --directory)
+    Info,
+    /// Decrypts every entry to check that nothing is corrupted (needs --directory)
+    Verify,
+    /// Changes the master password, the new one is read from stdin when it's piped (needs
+    /// --directory)
+    Passwd,
+    /// Saves a vault in the newest format, KDF options that aren't given stay the same (needs
+    /// --directory)
+    Upgrade {
+        #[command(flatten, next_help_heading = "KDF options")]
+        kdf: KdfArgs,
+    },
+    /// Opens the vault in a full-screen interface (a list of the services, the details of the
+    /// chosen one and a status bar)
+    Tui,
+    /// Clears the clipboard after a while (started in the background after copying)
+    #[command(hide = true)]
+    ClearClipboard {
+        #[arg(long)]
+        after: u64,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct KdfArgs {
+    #[arg(long, value_parser = Kdf::NAMES)]
+    /// The Argon2 variant (default: argon2id)
+    kdf: Option<String>,
+
+    #[arg(long)]
+    /// Memory used to derive a key in KiB (default: 65536)
+    memory_cost: Option<u32>,
+
+    #[arg(long)]
+    /// Amount of iterations (default: 3)
+    time_cost: Option<u32>,
+
+    #[arg(long)]
+    /// Amount of lanes (default: 1)
+    parallelism: Option<u32>,
+
+    #[arg(long, value_parser = Cipher::NAMES)]
+    /// The cipher of the entries (only fernet for now)
+    cipher: Option<String>,
+}
+
+impl KdfArgs {
+    /// `settings` in the newest format with the options that were given
+    fn apply(self, settings: VaultSettings) -> Result<VaultSettings, String> {
+        let mut settings = VaultSettings {
+            version: FORMAT_VERSION,
+            ..settings
+        };
+        if let Some(kdf) = self.kdf {
+            settings.kdf.kdf = Kdf::parse(&kdf)?;
+        }
+        if let Some(memory_cost) = self.memory_cost {
+            settings.kdf.memory_cost = memory_cost;
+        }
+        if let Some(time_cost) = self.time_cost {
+            settings.kdf.time_cost = time_cost;
+        }
+        if let Some(parallelism) = self.parallelism {
+            settings.kdf.parallelism = parallelism;
+        }
+        if let Some(cipher) = self.cipher {
+            settings.cipher = Cipher::parse(&cipher)?;
+        }
+        settings.kdf.validate()?;
+        Ok(settings)
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct GenerateArgs {
+    #[arg(long)]
+    /// Generate a passphrase instead of a random password
+    passphrase: bool,
+
+    #[arg(long, conflicts_with = "passphrase")]
+    /// Generate a password that follows a passwordrules string
+    /// ("required: lower; required: digit; minlength: 12")
+    rules: Option<String>,
+
+    #[arg(long, conflicts_with_all = ["passphrase", "rules"])]
+    /// Generate a password from a template (c/C: consonant, v/V: vowel, a/A: letter, 9: digit,
+    /// !: symbol, \x: x as is, anything else is kept)
+    pattern: Option<String>,
+
+    #[arg(long, conflicts_with_all = ["passphrase", "rules", "pattern"])]
+    /// Generate a pronounceable password made of syllables
+    pronounceable: bool,
+
+    #[command(flatten)]
+    policy: PolicyArgs,
+
+    #[command(flatten, next_help_heading = "Passphrase options")]
+    passphrase_policy: PassphraseArgs,
+
+    #[command(flatten, next_help_heading = "Pronounceable options")]
+    pronounceable_policy: PronounceableArgs,
+}
+
+impl TryFrom<GenerateArgs> for Generator {
+    type Error = String;
+    fn try_from(args: GenerateArgs) -> Result<Self, Self::Error> {
+        if args.passphrase {
+            Ok(Generator::Passphrase(PassphrasePolicy::try_from(
+                args.passphrase_policy,
+            )?))
+        } else if let Some(rules) = args.rules {
+            Ok(Generator::Rules(PasswordRules::parse(&rules)?))
+        } else if let Some(template) = args.pattern {
+            Ok(Generator::Pattern(PatternPolicy { template }))
+        } else if args.pronounceable {
+            Ok(Generator::Pronounceable(PronounceablePolicy {
+                syllables: args.pronounceable_policy.syllables,
+                digits: args.pronounceable_policy.trailing_digits,
+                capitalize: args.pronounceable_policy.capitalize,
+            }))
+        } else {
+            Ok(Generator::Random(PasswordPolicy::from(args.policy)))
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct PronounceableArgs {
+    #[arg(long, default_value_t = 6)]
+    /// Amount of syllables
+    syllables: usize,
+
+    #[arg(long, default_value_t = 2)]
+    /// Amount of digits at the end
+    trailing_digits: usize,
+
+    #[arg(long)]
+    /// Make the first letter uppercase
+    capitalize: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct PassphraseArgs {
+    #[arg(short, long, default_value_t = 6)]
+    /// Amount of words
+    words: usize,
+
+    #[arg(long, default_value_t = String::from("-"))]
+    /// Put between the words
+    separator: String,
+
+    #[arg(long, default_value_t = String::from("lower"))]
+    /// lower, upper, title or random
+    capitalization: String,
+
+    #[arg(long)]
+    /// Add a digit to a random word
+    digit: bool,
+
+    #[arg(long)]
+    /// Add a symbol to a random word
+    symbol: bool,
+
+    #[arg(long, default_value_t = String::from("large"))]
+    /// large, short or a path to a wordlist (one word per line, EFF wordlists work too)
+    wordlist: String,
+}
+
+impl TryFrom<PassphraseArgs> for PassphrasePolicy {
+    type Error = String;
+    fn try_from(args: PassphraseArgs) -> Result<Self, Self::Error> {
+        Ok(PassphrasePolicy {
+            words: args.words,
+            separator: args.separator,
+            capitalization: Capitalization::parse(&args.capitalization)?,
+            digit: args.digit,
+            symbol: args.symbol,
+            wordlist: Wordlist::parse(&args.wordlist)?,
+        })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct PolicyArgs {
+    #[arg(short, long, default_value_t = 14)]
+    /// Length of the password
+    length: usize,
+
+    #[arg(long, default_value_t = String::from("luds"))]
+    /// Character classes to use (l: lowercase, u: uppercase, d: digits, s: symbols)
+    classes: String,
+
+    #[arg(long, default_value_t = 1)]
+    /// Minimum amount of characters from each class
+    min: usize,
+
+    #[arg(long)]
+    /// Minimum amount of lowercase letters (overrides --min)
+    min_lowercase: Option<usize>,
+
+    #[arg(long)]
+    /// Minimum amount of uppercase letters (overrides --min)
+    min_uppercase: Option<usize>,
+
+    #[arg(long)]
+    /// Minimum amount of digits (overrides --min)
+    min_digits: Option<usize>,
+
+    #[arg(long)]
+    /// Minimum amount of symbols (overrides --min)
+    min_symbols: Option<usize>,
+
+    #[arg(short, long, default_value_t = String::new())]
+    /// Characters that are never used
+    exclude: String,
+
+    #[arg(long)]
+    /// Leave out look-alikes (0O1lI)
+    no_look_alikes: bool,
+
+    #[arg(long)]
+    /// Leave out repeated characters (aa)
+    no_repeats: bool,
+
+    #[arg(long)]
+    /// Leave out sequences (abc, 321)
+    no_sequences: bool,
+}
+
+impl From<PolicyArgs> for PasswordPolicy {
+    fn from(args: PolicyArgs) -> Self {
+        let classes = CharClass::ALL
+            .into_iter()
+            .filter(|class| args.classes.contains(class.letter()))
+            .map(|class| {
+                let minimum = match class {
+                    CharClass::Lowercase => args.min_lowercase,
+                    CharClass::Uppercase => args.min_uppercase,
+                    CharClass::Digits => args.min_digits,
+                    CharClass::Symbols => args.min_symbols,
+                };
+                (class, minimum.unwrap_or(args.min))
+            })
+            .collect();
+        PasswordPolicy {
+            length: args.length,
+            classes,
+            exclude: args.exclude,
+            no_look_alikes: args.no_look_alikes,
+            no_repeats: args.no_repeats,
+            no_sequences: args.no_sequences,
+        }
+    }
 }
 
 fn main() {
-    let mut modified = false;
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(Command::ClearClipboard { after }) = args.command {
+        clear_clipboard_after(after);
+        return;
+    }
+    let config = Config::load(args.config.as_deref()).unwrap_or_else(|error| {
+        eprintln!("{RED}Error: {error}{RESET}");
+        exit(exitcode::USAGE)
+    });
+    if args.directory == "\0\0\0\0"
+        && let Some(directory) = config.directory
+    {
+        args.directory = directory;
+    }
+    let breach_db = args.breach_db.take().or(config.breach_db);
+    let clip_timeout = args
+        .clip_timeout
+        .or(config.clip_timeout)
+        .unwrap_or(DEFAULT_CLIP_TIMEOUT);
+    let breach_database = breach_db.as_deref().map(|path| {
+        BreachDatabase::open(path).unwrap_or_else(|error| {
+            eprintln!("{RED}Error: {error}{RESET}");
+            exit(exitcode::USAGE)
+        })
+    });
+    let password_source = if let Some(fd) = args.password_fd {
+        Some(PasswordSource::Fd(fd))
+    } else if let Some(path) = args.password_file.take() {
+        Some(PasswordSource::File(path))
+    } else if let Some(variable) = args.password_env.take() {
+        Some(PasswordSource::Env(variable))
+    } else {
+        args.password_command.take().map(PasswordSource::Command)
+    };
+    let tui = matches!(args.command, Some(Command::Tui));
+    if let Some(command) = args.command.take().filter(|_| !tui) {
+        run_command(
+            command,
+            &args.directory,
+            &args.format,
+            clip_timeout,
+            args.reveal_all,
+            breach_database.as_ref(),
+            password_source.as_ref(),
+        );
+        return;
+    }
     let mut menu = Menu::new(
         MenuConfig {
             prompt: args.prompt,
@@ -65,42 +560,133 @@ fn main() {
             "3. Remove a password".to_string(),
             "4. List passwords".to_string(),
             "5. Generate password".to_string(),
-            "6. Save & quit".to_string(),
-            "7. Quit".to_string(),
+            "6. Regenerate a password".to_string(),
+            "7. Add 2FA secret".to_string(),
+            "8. Show 2FA code".to_string(),
+            "9. Import 2FA seeds".to_string(),
+            "10. Audit vault".to_string(),
+            "11. Set password expiry".to_string(),
+            "12. Rotate a password".to_string(),
+            "13. Rotate many passwords".to_string(),
+            "14. Edit tags".to_string(),
+            "15. Rename a service".to_string(),
+            "16. Copy a password".to_string(),
+            "17. Undo".to_string(),
+            "18. Redo".to_string(),
+            "19. Review changes".to_string(),
+            "20. Save".to_string(),
+            "21. Lock vault".to_string(),
+            "22. Close vault".to_string(),
+            "23. Open another vault".to_string(),
+            "24. Save & quit".to_string(),
+            "25. Quit".to_string(),
         ],
     );
-    let (directory, master_password, is_new) = {
+    let (mut directory, mut master_password, mut is_new) = {
         if &args.directory == "\0\0\0\0" {
-            let result = directory_selector(args.format_string);
-            if result.is_err() {
-                eprintln!("Something went wrong: {}", result.unwrap_err());
-                exit(1)
-            }
-            result.unwrap()
-        } else if !verify_directory(&args.directory) {
-            eprintln!(
-                "{RED}Error: The directory provided either doesn't have the correct structure or it doesn't exist{RESET}"
-            );
-            exit(1);
+            match directory_selector(args.format_string.clone(), breach_database.as_ref()) {
+                Ok(result) => result,
+                Err(error) => {
+                    eprintln!("Something went wrong: {error}");
+                    exit(exitcode::ERROR)
+                }
+            }
         } else {
-            let master_password = prompt_master_password(&args.directory);
+            require_directory(&args.directory);
+            let master_password = master_password(&args.directory, password_source.as_ref());
             (args.directory, master_password, false)
         }
     };
-    let mut password_array = PasswordArray::new(master_password, directory);
-    if !is_new {
-        let result = password_array.load(true);
-        if result.is_err() {
-            let error = result.unwrap_err();
+    set_lock_timeout(
+        args.lock_timeout
+            .or(config.lock_timeout)
+            .unwrap_or(DEFAULT_LOCK_TIMEOUT),
+    );
+    // one vault after another until nspm quits
+    loop {
+        let mut password_array = PasswordArray::new(master_password, directory);
+        let mut modified = false;
+        if !is_new && let Err(error) = password_array.load(true) {
             eprintln!("{error}")
         }
-    }
-    loop {
-        run(menu.interact(), &mut password_array, &mut modified);
+        password_array.enable_journal();
+        if is_new {
+            // left over from a vault that was in the same place
+            discard_journal(&mut password_array);
+        } else if password_array.has_journal() {
+            while replay_journal(&mut password_array, &mut modified).is_err() {
+                lock_vault(&mut password_array, true);
+            }
+        }
+        if print_expiry_warnings(&password_array) && pause().is_err() {
+            lock_vault(&mut password_array, true);
+        }
+        enter_alternate_screen();
+        if tui {
+            while Tui::new(
+                &mut password_array,
+                &mut modified,
+                clip_timeout,
+                breach_database.as_ref(),
+            )
+            .interact()
+            .is_err()
+            {
+                lock_vault(&mut password_array, true);
+            }
+            discard_journal(&mut password_array);
+            leave_alternate_screen();
+            return;
+        }
+        let next = loop {
+            let session = menu.interact().and_then(|index| {
+                match session_action(index, &password_array, modified)? {
+                    Some(session) => Ok(session),
+                    None => run(
+                        index,
+                        &mut password_array,
+                        &mut modified,
+                        clip_timeout,
+                        args.reveal_all,
+                        breach_database.as_ref(),
+                    )
+                    .map(|()| Session::Continue),
+                }
+            });
+            match session {
+                Err(Locked) => lock_vault(&mut password_array, true),
+                Ok(Session::Continue) => {}
+                Ok(Session::Lock) => lock_vault(&mut password_array, false),
+                Ok(Session::Close) => {
+                    discard_journal(&mut password_array);
+                    break directory_selector(args.format_string.clone(), breach_database.as_ref())
+                        .unwrap_or_else(|error| {
+                            leave_alternate_screen();
+                            eprintln!("Something went wrong: {error}");
+                            exit(exitcode::ERROR)
+                        });
+                }
+                Ok(Session::Open(directory)) => {
+                    discard_journal(&mut password_array);
+                    let master_password = prompt_master_password(&directory);
+                    break (directory, master_password, false);
+                }
+            }
+        };
+        // the secrets of the old vault are zeroized when it's dropped
+        drop(password_array);
+        (directory, master_password, is_new) = next;
     }
 }
 
-fn run(index: usize, password_array: &mut PasswordArray, password_array_modified: &mut bool) {
+fn run(
+    index: usize,
+    password_array: &mut PasswordArray,
+    password_array_modified: &mut bool,
+    clip_timeout: u64,
+    reveal_all: bool,
+    breach_database: Option<&BreachDatabase>,
+) -> Result<(), Locked> {
     match index {
         0 => {
             let service = input(
@@ -108,107 +694,1287 @@ fn run(index: usize, password_array: &mut PasswordArray, password_array_modified
                 String::new(),
                 &password_array.get_services(),
                 &ALL_FLAGS,
-            );
-            let password = new_password_input("Password: ");
+            )?;
+            let password = new_password_input("Password: ", breach_database)?;
             let result = password_array.add_password(service, password);
-            if result.is_err() {
-                println!("{}", result.unwrap_err());
-                pause();
-                return;
+            if let Err(error) = result {
+                println!("{error}");
+                return pause();
             }
             *password_array_modified = true
         }
         1 => {
-            let service = input(
-                "Service: ",
-                String::new(),
-                &password_array.get_services(),
-                &[InputFlags::HighlightInput, InputFlags::DenyEmptyInput],
-            );
-            let new_password = new_password_input("Password: ");
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            let new_password = new_password_input("Password: ", breach_database)?;
             let result = password_array.edit_password(service, new_password);
-            if result.is_err() {
-                println!("{}", result.unwrap_err());
-                pause();
-                return;
+            if let Err(error) = result {
+                println!("{error}");
+                return pause();
             }
             *password_array_modified = true
         }
         2 => {
-            let service = input(
-                "Service: ",
-                String::new(),
-                &password_array.get_services(),
-                &[InputFlags::HighlightInput, InputFlags::DenyEmptyInput],
-            );
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
             let result = password_array.remove_password(service);
-            if result.is_err() {
-                println!("{}", result.unwrap_err());
-                pause();
-                return;
+            if let Err(error) = result {
+                println!("{error}");
+                return pause();
             }
             *password_array_modified = true
         }
-        3 => {
-            let table = password_array.table();
+        3 if reveal_all => {
+            let table = password_array.table(true);
             println!("{table}");
             drop(table);
-            pause();
+            pause()?;
+        }
+        3 => {
+            let mut entries = vec![];
+            for service in password_array.get_services() {
+                match password_array.get_password(&service) {
+                    Ok(password) => entries.push((service, password.clone())),
+                    Err(error) => {
+                        println!("{error}");
+                        return pause();
+                    }
+                }
+            }
+            PasswordList::new(
+                MenuConfig {
+                    prompt: String::from("Passwords"),
+                    icon: String::from(">"),
+                },
+                entries,
+                clip_timeout,
+            )
+            .interact()?;
         }
         4 => {
-            let generated_password = generate_password(
-                prompt_number("Length of generated password: ", "14".to_string()).into(),
-            );
+            let generator = prompt_generator()?;
+            let generated_password = match generate_password(&generator) {
+                Ok(password) => password,
+                Err(error) => {
+                    println!("{error}");
+                    return pause();
+                }
+            };
             println!("\nGenerated password: {generated_password}");
+            println!("Entropy: {:.1} bits", generator.entropy());
             let answer = input(
                 "Do you want to add this password? ",
                 "yes".to_string(),
                 NO_COMMANDS,
                 NO_FLAGS,
-            );
+            )?;
             if YESES.iter().any(|y| *y == answer.to_lowercase().trim()) {
                 let service = input(
                     "Service: ",
                     String::new(),
                     &password_array.get_services(),
                     &ALL_FLAGS,
-                );
-                let res =
-                    password_array.add_password(service, SecretString::from(generated_password));
-                if res.is_err() {
-                    println!("{}", res.unwrap_err());
-                    pause();
-                    return;
+                )?;
+                let res = password_array
+                    .add_password(service.clone(), SecretString::from(generated_password));
+                if let Err(error) = res {
+                    println!("{error}");
+                    return pause();
+                }
+                *password_array_modified = true;
+                if let Generator::Rules(rules) = &generator
+                    && let Err(error) = password_array.set_password_rules(&service, Some(rules))
+                {
+                    println!("{error}");
+                    return pause();
                 }
-                *password_array_modified = true
             }
         }
         5 => {
-            let result = password_array.save(true);
-            if result.is_err() {
-                let error = result.unwrap_err();
-                eprintln!("\n{error}");
-                exit(1)
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            let generator = match password_array.get_password_rules(&service) {
+                Ok(Some(rules)) => {
+                    println!("Using the passwordrules of {service}: {rules}");
+                    Generator::Rules(rules)
+                }
+                Ok(None) => prompt_generator()?,
+                Err(error) => {
+                    println!("{error}");
+                    return pause();
+                }
+            };
+            let generated_password = match generate_password(&generator) {
+                Ok(password) => password,
+                Err(error) => {
+                    println!("{error}");
+                    return pause();
+                }
+            };
+            println!("\nGenerated password: {generated_password}");
+            println!("Entropy: {:.1} bits", generator.entropy());
+            if !confirm("Do you want to replace the old password? ", "yes")? {
+                return Ok(());
+            }
+            let result = password_array
+                .edit_password(service.clone(), SecretString::from(generated_password));
+            if let Err(error) = result {
+                println!("{error}");
+                return pause();
+            }
+            *password_array_modified = true;
+            if let Generator::Rules(rules) = &generator
+                && let Err(error) = password_array.set_password_rules(&service, Some(rules))
+            {
+                println!("{error}");
+                return pause();
             }
-            exit(0)
         }
         6 => {
-            if *password_array_modified {
-                let answer = input(
-                    "You have some unsaved changes, are you sure? ",
-                    "no".to_string(),
-                    NO_COMMANDS,
-                    NO_FLAGS,
-                );
-                if YESES.iter().any(|y| *y == answer.to_lowercase().trim()) {
-                    exit(0)
-                } else {
-                    return;
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            let secret = input(
+                "otpauth:// URI or base32 secret: ",
+                String::new(),
+                NO_COMMANDS,
+                &[InputFlags::DenyEmptyInput],
+            )?;
+            let otp = if secret.trim().starts_with("otpauth://") {
+                Otp::from_uri(&secret)
+            } else {
+                Otp::from_base32(&secret, service.clone(), String::new())
+            };
+            let result = otp.and_then(|otp| {
+                password_array
+                    .set_otp(service, &otp)
+                    .map_err(|err| err.to_string())
+            });
+            if let Err(error) = result {
+                println!("{error}");
+                return pause();
+            }
+            *password_array_modified = true
+        }
+        7 => {
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            let otp = match password_array.get_otp(&service) {
+                Ok(otp) => otp,
+                Err(error) => {
+                    println!("{error}");
+                    return pause();
                 }
+            };
+            if otp.remaining().is_some() {
+                let key = live_view(
+                    || {
+                        let code = otp.clone().generate();
+                        let remaining = otp.remaining().unwrap_or_default();
+                        let color = if remaining <= 5 { YELLOW } else { GREEN };
+                        format!(
+                            "{BOLD}{service}{RESET} {}\n\n{color}{code}{RESET} (expires in {remaining}s)\n",
+                            otp.issuer
+                        )
+                    },
+                    "Press c to copy the code, any other key to continue...",
+                )?;
+                if key == Some(Key::Char('c')) {
+                    if let Err(error) = copy(&otp.clone().generate(), "The code", clip_timeout) {
+                        println!("{error}");
+                    }
+                    pause()?;
+                }
+                return Ok(());
+            }
+            match password_array.otp_code(&service) {
+                Ok(code) => {
+                    println!(
+                        "{BOLD}{service}{RESET} {}\n\n{GREEN}{code}{RESET}\n",
+                        otp.issuer
+                    );
+                    if confirm("Copy the code? ", "no")?
+                        && let Err(error) = copy(&code, "The code", clip_timeout)
+                    {
+                        println!("{error}");
+                    }
+                }
+                Err(error) => println!("{error}"),
+            }
+            pause()?;
+        }
+        8 => {
+            let uri = input(
+                "otpauth-migration:// or otpauth:// URI: ",
+                String::new(),
+                NO_COMMANDS,
+                &[InputFlags::DenyEmptyInput],
+            )?;
+            match Otp::import(&uri) {
+                Ok(otps) => {
+                    let (imported, duplicates) = password_array.import_otps(otps);
+                    print_import_report(&imported, &duplicates);
+                    if !imported.is_empty() {
+                        *password_array_modified = true
+                    }
+                }
+                Err(error) => println!("{error}"),
+            }
+            pause()?;
+        }
+        9 => {
+            let max_age = prompt_number(
+                "Report passwords not changed in how many days? ",
+                DEFAULT_MAX_AGE_DAYS.to_string(),
+            )?;
+            match AuditReport::run(password_array, max_age.into(), breach_database) {
+                Ok(report) => {
+                    println!("{}", report.table());
+                    println!("{}", report.summary())
+                }
+                Err(error) => println!("{error}"),
+            }
+            pause()?;
+        }
+        10 => {
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            match password_array.get_expiry(&service) {
+                Ok(Some(expiry)) => println!("Current expiry: {expiry}"),
+                Ok(None) => {}
+                Err(error) => {
+                    println!("{error}");
+                    return pause();
+                }
+            }
+            let answer = input(
+                "Expiry date (YYYY-MM-DD), rotation interval (like 90d) or nothing to remove it: ",
+                String::new(),
+                NO_COMMANDS,
+                NO_FLAGS,
+            )?;
+            let expiry = if answer.trim().is_empty() {
+                None
             } else {
-                exit(0)
+                match Expiry::parse(&answer) {
+                    Ok(expiry) => Some(expiry),
+                    Err(error) => {
+                        println!("{error}");
+                        return pause();
+                    }
+                }
+            };
+            if let Err(error) = password_array.set_expiry(&service, expiry) {
+                println!("{error}");
+                return pause();
+            }
+            if let Ok(Some(expires_at)) = password_array.expires_at(&service) {
+                println!(
+                    "The password of {service} {}",
+                    rotation::describe(expires_at)
+                );
+                pause()?;
+            }
+            *password_array_modified = true
+        }
+        11 => rotate(password_array, password_array_modified)?,
+        12 => {
+            let tag = input(
+                "Tag (nothing to pick the services yourself): ",
+                String::new(),
+                NO_COMMANDS,
+                NO_FLAGS,
+            )?;
+            let services = password_array.get_services();
+            if services.is_empty() {
+                println!("There are no services");
+                return pause();
+            }
+            let tagged = password_array.services_with_tag(tag.trim());
+            let checked = services.iter().map(|s| tagged.contains(s)).collect();
+            let mut select = MultiSelect::new(
+                MenuConfig {
+                    prompt: String::from("Services to rotate"),
+                    icon: String::from(">"),
+                },
+                services.clone(),
+                checked,
+            );
+            let chosen: Vec<String> = select
+                .interact()?
+                .into_iter()
+                .map(|index| services[index].clone())
+                .collect();
+            if chosen.is_empty() {
+                return Ok(());
+            }
+            if let Err(error) = bulk_rotate(password_array, chosen) {
+                println!("{error}");
+            }
+            *password_array_modified = password_array.has_unsaved_changes();
+            pause()?;
+        }
+        13 => {
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            let tags = match password_array.get_tags(&service) {
+                Ok(tags) => tags.join(", "),
+                Err(error) => {
+                    println!("{error}");
+                    return pause();
+                }
+            };
+            let answer = input("Tags (separated by commas): ", tags, NO_COMMANDS, NO_FLAGS)?;
+            let tags: Vec<String> = answer.split(',').map(|tag| tag.to_string()).collect();
+            if let Err(error) = password_array.set_tags(&service, &tags) {
+                println!("{error}");
+                return pause();
+            }
+            *password_array_modified = true
+        }
+        14 => {
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            let new_name = input(
+                "New name: ",
+                service.clone(),
+                &password_array.get_services(),
+                &ALL_FLAGS,
+            )?;
+            if let Err(error) = password_array.rename_service(&service, new_name) {
+                println!("{error}");
+                return pause();
+            }
+            *password_array_modified = true
+        }
+        15 => {
+            let Some(service) = pick_service(password_array)? else {
+                return Ok(());
+            };
+            let result = password_array.get_password(&service).and_then(|password| {
+                copy(
+                    password.expose_secret(),
+                    &format!("The password of {service}"),
+                    clip_timeout,
+                )
+            });
+            if let Err(error) = result {
+                println!("{error}");
+            }
+            pause()?;
+        }
+        16 => {
+            match password_array.undo() {
+                Some(description) => println!("Undid: {description}"),
+                None => println!("There's nothing to undo"),
+            }
+            *password_array_modified = password_array.has_unsaved_changes();
+            pause()?;
+        }
+        17 => {
+            match password_array.redo() {
+                Some(description) => println!("Redid: {description}"),
+                None => println!("There's nothing to redo"),
+            }
+            *password_array_modified = password_array.has_unsaved_changes();
+            pause()?;
+        }
+        18 => review_changes(password_array, password_array_modified)?,
+        19 => {
+            if let Err(error) = password_array.save(true) {
+                println!("{error}");
+                return pause();
+            }
+            *password_array_modified = false
+        }
+        23 => {
+            if let Err(error) = password_array.save(true) {
+                leave_alternate_screen();
+                eprintln!("{error}");
+                exit(exitcode::IO)
             }
+            quit(0)
+        }
+        24 if discard_changes(password_array, *password_array_modified)? => {
+            discard_journal(password_array);
+            quit(0)
         }
         _ => {}
     }
+    Ok(())
+}
+
+/// What happens to the open vault after an action in the menu
+enum Session {
+    Continue,
+    /// Lock it until the master password is typed in again
+    Lock,
+    /// Close it and choose another one with [directory_selector]
+    Close,
+    /// Close it and open this one
+    Open(String),
+}
+
+/// The actions of the menu that close or lock the vault (None for the others, they're done by
+/// [run])
+fn session_action(
+    index: usize,
+    password_array: &PasswordArray,
+    password_array_modified: bool,
+) -> Result<Option<Session>, Locked> {
+    Ok(match index {
+        20 => Some(Session::Lock),
+        21 if discard_changes(password_array, password_array_modified)? => Some(Session::Close),
+        22 => {
+            let directory = input(
+                "Vault: ",
+                String::new(),
+                NO_COMMANDS,
+                &[InputFlags::DenyEmptyInput],
+            )?;
+            let directory = directory.trim().to_string();
+            if !verify_directory(&directory) {
+                println!("{RED}{directory} isn't a vault{RESET}");
+                pause()?;
+                return Ok(Some(Session::Continue));
+            }
+            if !discard_changes(password_array, password_array_modified)? {
+                return Ok(Some(Session::Continue));
+            }
+            Some(Session::Open(directory))
+        }
+        21 => Some(Session::Continue),
+        _ => None,
+    })
+}
+
+/// Offers to replay the changes a session that didn't end (nspm crashed, the terminal was closed)
+/// left in the journal of the vault, it's only [Locked] before anything is replayed or thrown away
+fn replay_journal(
+    password_array: &mut PasswordArray,
+    password_array_modified: &mut bool,
+) -> Result<(), Locked> {
+    println!(
+        "{YELLOW}{} has changes that weren't saved from a session that didn't end{RESET}",
+        password_array.get_directory()
+    );
+    let answer = input(
+        "Replay them (no throws them away)? ",
+        "yes".to_string(),
+        NO_COMMANDS,
+        NO_FLAGS,
+    )?;
+    if !YESES.iter().any(|y| *y == answer.to_lowercase().trim()) {
+        discard_journal(password_array);
+        return Ok(());
+    }
+    if let Err(error) = password_array.replay_journal() {
+        println!("{RED}{error}{RESET}");
+        let answer = input(
+            "Throw the journal away (no quits)? ",
+            "no".to_string(),
+            NO_COMMANDS,
+            NO_FLAGS,
+        )?;
+        if !YESES.iter().any(|y| *y == answer.to_lowercase().trim()) {
+            exit(exitcode::CORRUPT)
+        }
+        discard_journal(password_array);
+        return Ok(());
+    }
+    for change in password_array.changes() {
+        println!("{change}");
+    }
+    *password_array_modified = password_array.has_unsaved_changes();
+    if pause().is_err() {
+        lock_vault(password_array, true);
+    }
+    Ok(())
+}
+
+/// Throws the journal away when a vault is closed without saving on purpose
+fn discard_journal(password_array: &mut PasswordArray) {
+    if let Err(error) = password_array.discard_journal() {
+        eprintln!("{RED}{error}{RESET}");
+    }
+}
+
+/// Lists the unsaved changes and asks if they can be thrown away (true when there are none)
+fn discard_changes(
+    password_array: &PasswordArray,
+    password_array_modified: bool,
+) -> Result<bool, Locked> {
+    if !password_array_modified {
+        return Ok(true);
+    }
+    for change in password_array.changes() {
+        println!("{change}");
+    }
+    let answer = input(
+        "You have some unsaved changes, are you sure? ",
+        "no".to_string(),
+        NO_COMMANDS,
+        NO_FLAGS,
+    )?;
+    Ok(YESES.iter().any(|y| *y == answer.to_lowercase().trim()))
+}
+
+fn run_command(
+    command: Command,
+    directory: &str,
+    format: &str,
+    clip_timeout: u64,
+    reveal_all: bool,
+    breach_database: Option<&BreachDatabase>,
+    password_source: Option<&PasswordSource>,
+) {
+    match command {
+        Command::Add {
+            service,
+            generate,
+            tag,
+        } => {
+            let mut password_array = open_vault(directory, password_source);
+            if password_array.get_services().contains(&service) {
+                eprintln!("{RED}Error: service name is taken{RESET}");
+                exit(exitcode::CONFLICT)
+            }
+            let result = read_new_password(&password_array, &service, generate, breach_database)
+                .and_then(|password| {
+                    password_array.add_password(service.clone(), password)?;
+                    Ok(password_array.set_tags(&service, &tag)?)
+                });
+            if let Err(error) = result {
+                eprintln!("{error}");
+                exit(exitcode::ERROR)
+            }
+            save_vault(&mut password_array);
+        }
+        Command::Get { service, clip } => {
+            let password_array = open_vault(directory, password_source);
+            require_service(&password_array, &service);
+            match password_array.get_password(&service) {
+                Ok(password) if clip => {
+                    let what = format!("The password of {service}");
+                    if let Err(error) = copy(password.expose_secret(), &what, clip_timeout) {
+                        eprintln!("{RED}Error: {error}{RESET}");
+                        exit(exitcode::ERROR)
+                    }
+                }
+                Ok(password) => println!("{}", password.expose_secret()),
+                Err(error) => {
+                    eprintln!("{error}");
+                    exit(exitcode::NOT_FOUND)
+                }
+            }
+        }
+        Command::Edit { service, generate } => {
+            let mut password_array = open_vault(directory, password_source);
+            require_service(&password_array, &service);
+            let result = read_new_password(&password_array, &service, generate, breach_database)
+                .and_then(|password| Ok(password_array.edit_password(service.clone(), password)?));
+            if let Err(error) = result {
+                eprintln!("{error}");
+                exit(exitcode::ERROR)
+            }
+            save_vault(&mut password_array);
+        }
+        Command::Rm { service } => {
+            let mut password_array = open_vault(directory, password_source);
+            require_service(&password_array, &service);
+            if let Err(error) = password_array.remove_password(service) {
+                eprintln!("{error}");
+                exit(exitcode::NOT_FOUND)
+            }
+            save_vault(&mut password_array);
+        }
+        Command::Mv { service, new_name } => {
+            let mut password_array = open_vault(directory, password_source);
+            require_service(&password_array, &service);
+            if let Err(error) = password_array.rename_service(&service, new_name) {
+                eprintln!("{RED}Error: {error}{RESET}");
+                exit(exitcode::CONFLICT)
+            }
+            save_vault(&mut password_array);
+        }
+        Command::Ls { tag } => {
+            let password_array = open_vault(directory, password_source);
+            let services = match tag {
+                Some(tag) => password_array.services_with_tag(&tag),
+                None => password_array.get_services(),
+            };
+            if format == "json" {
+                let entries: Result<Vec<Value>, String> = services
+                    .iter()
+                    .map(|service| details_json(&password_array, service, reveal_all))
+                    .collect();
+                match entries {
+                    Ok(entries) => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&entries).unwrap_or_default()
+                    ),
+                    Err(error) => {
+                        eprintln!("{error}");
+                        exit(exitcode::CORRUPT)
+                    }
+                }
+                return;
+            }
+            for service in services {
+                match password_array.get_password(&service) {
+                    Ok(password) if reveal_all => {
+                        println!("{service}\t{}", password.expose_secret())
+                    }
+                    _ => println!("{service}"),
+                }
+            }
+        }
+        Command::Pick { query } => {
+            let password_array = open_vault(directory, password_source);
+            if password_array.get_services().is_empty() {
+                eprintln!("{RED}Error: {directory} doesn't have any services{RESET}");
+                exit(exitcode::NOT_FOUND);
+            }
+            match without_lock(|| service_picker(&password_array).interact(query.clone())) {
+                Some(service) => println!("{service}"),
+                None => exit(exitcode::ERROR),
+            }
+        }
+        Command::Show { service, reveal } => {
+            let password_array = open_vault(directory, password_source);
+            require_service(&password_array, &service);
+            let result = if format == "json" {
+                details_json(&password_array, &service, reveal).map(|details| {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&details).unwrap_or_default()
+                    )
+                })
+            } else {
+                print_details(&password_array, &service, reveal)
+            };
+            if let Err(error) = result {
+                eprintln!("{error}");
+                exit(exitcode::CORRUPT)
+            }
+        }
+        Command::Otp { service, clip } => {
+            let mut password_array = open_vault(directory, password_source);
+            require_service(&password_array, &service);
+            if let Err(error) = password_array.get_otp(&service) {
+                eprintln!("{error}");
+                exit(exitcode::NOT_FOUND)
+            }
+            // only fails now when the HOTP counter can't be written
+            match password_array.otp_code(&service) {
+                Ok(code) => {
+                    if !clip {
+                        println!("{code}")
+                    } else if let Err(error) = copy(&code, "The code", clip_timeout) {
+                        eprintln!("{RED}Error: {error}{RESET}");
+                        exit(exitcode::ERROR)
+                    }
+                }
+                Err(error) => {
+                    eprintln!("{error}");
+                    exit(exitcode::IO)
+                }
+            }
+        }
+        Command::Generate(generate_args) => {
+            let result = Generator::try_from(*generate_args)
+                .and_then(|generator| Ok((generate_password(&generator)?, generator.entropy())));
+            match result {
+                Ok((password, entropy)) => {
+                    println!("{password}");
+                    eprintln!("Entropy: {entropy:.1} bits")
+                }
+                Err(error) => {
+                    eprintln!("{error}");
+                    exit(exitcode::USAGE)
+                }
+            }
+        }
+        Command::ImportOtp { file } => {
+            let contents = fs::read_to_string(&file).unwrap_or_else(|err| {
+                eprintln!("Failed to read {file}, Error: {err}");
+                exit(exitcode::IO)
+            });
+            let mut otps = vec![];
+            for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+                match Otp::import(line) {
+                    Ok(mut o) => otps.append(&mut o),
+                    Err(error) => {
+                        eprintln!("{error}");
+                        exit(exitcode::ERROR)
+                    }
+                }
+            }
+            let mut password_array = open_vault(directory, password_source);
+            let (imported, duplicates) = password_array.import_otps(otps);
+            print_import_report(&imported, &duplicates);
+            if !imported.is_empty() {
+                save_vault(&mut password_array);
+            }
+        }
+        Command::Rotate {
+            tag,
+            pending,
+            services,
+        } => {
+            let mut password_array = open_vault(directory, password_source);
+            for service in &services {
+                require_service(&password_array, service);
+            }
+            let mut chosen = services;
+            if let Some(tag) = tag {
+                let tagged = password_array.services_with_tag(&tag);
+                if tagged.is_empty() {
+                    eprintln!("{RED}Error: No service has the tag {tag}{RESET}");
+                    exit(exitcode::NOT_FOUND)
+                }
+                for service in tagged {
+                    if !chosen.contains(&service) {
+                        chosen.push(service);
+                    }
+                }
+            }
+            if pending {
+                if chosen.is_empty() {
+                    chosen = password_array.get_services();
+                }
+                chosen.retain(|service| matches!(password_array.get_pending(service), Ok(Some(_))));
+                if chosen.is_empty() {
+                    println!("Nothing is pending");
+                    return;
+                }
+            }
+            if chosen.is_empty() {
+                let services = password_array.get_services();
+                if services.is_empty() {
+                    eprintln!("{RED}Error: There are no services{RESET}");
+                    exit(exitcode::NOT_FOUND);
+                }
+                let mut select = MultiSelect::new(
+                    MenuConfig {
+                        prompt: String::from("Services to rotate"),
+                        icon: String::from(">"),
+                    },
+                    services.clone(),
+                    vec![],
+                );
+                chosen = without_lock(|| select.interact())
+                    .into_iter()
+                    .map(|index| services[index].clone())
+                    .collect();
+            }
+            if let Err(error) = bulk_rotate(&mut password_array, chosen) {
+                eprintln!("{error}");
+                exit(exitcode::ERROR)
+            }
+        }
+        Command::Audit { max_age } => {
+            let password_array = open_vault(directory, password_source);
+            match AuditReport::run(&password_array, max_age, breach_database) {
+                Ok(report) if format == "json" => println!("{}", report.to_json()),
+                Ok(report) => {
+                    println!("{}", report.table());
+                    println!("{}", report.summary())
+                }
+                Err(error) => {
+                    eprintln!("{error}");
+                    exit(exitcode::ERROR)
+                }
+            }
+        }
+        Command::Init { path, kdf } => {
+            let settings = kdf.apply(VaultSettings::default()).unwrap_or_else(|error| {
+                eprintln!("{RED}Error: {error}{RESET}");
+                exit(exitcode::USAGE)
+            });
+            if fs::exists(&path).unwrap_or(true) {
+                eprintln!("{RED}Error: {path} already exists{RESET}");
+                exit(exitcode::CONFLICT)
+            }
+            let master_password = read_master_password(breach_database).unwrap_or_else(|error| {
+                eprintln!("{RED}Error: {error}{RESET}");
+                exit(exitcode::ERROR)
+            });
+            if let Err(error) =
+                initialize_directory(&path, master_password.expose_secret(), &settings)
+            {
+                eprintln!("{RED}Error: {error}{RESET}");
+                exit(exitcode::IO)
+            }
+            println!("Made a new vault at {path}");
+        }
+        Command::Info => {
+            require_directory(directory);
+            match VaultInfo::read(directory) {
+                Ok(info) if format == "json" => println!("{}", info.to_json()),
+                Ok(info) => {
+                    println!("Vault: {directory}");
+                    println!("Format version: {}", info.settings.version);
+                    println!("KDF: {}", info.settings.kdf);
+                    println!("Cipher: {}", info.settings.cipher);
+                    println!("Entries: {}", info.entries);
+                    println!("2FA secrets: {}", info.otps);
+                    if let Some(last_modified) = info.last_modified {
+                        println!("Last modified: {}", rotation::format_date(last_modified));
+                    }
+                    if info.settings.version < FORMAT_VERSION {
+                        println!(
+                            "{YELLOW}This vault can be upgraded to format version {FORMAT_VERSION} with nspm upgrade{RESET}"
+                        );
+                    }
+                }
+                Err(error) => {
+                    eprintln!("{RED}Error: {error}{RESET}");
+                    exit(exitcode::CORRUPT)
+                }
+            }
+        }
+        Command::Verify => {
+            require_directory(directory);
+            let master_password = master_password(directory, password_source);
+            match verify_vault(directory, &master_password) {
+                Ok((entries, problems)) if problems.is_empty() => {
+                    println!("{GREEN}✔{RESET} All {entries} entries can be decrypted")
+                }
+                Ok((entries, problems)) => {
+                    for problem in &problems {
+                        println!("{RED}✘{RESET} {problem}");
+                    }
+                    println!("{} of {entries} entries can't be read", problems.len());
+                    exit(exitcode::CORRUPT)
+                }
+                Err(error) => {
+                    eprintln!("{RED}Error: {error}{RESET}");
+                    exit(exitcode::CORRUPT)
+                }
+            }
+        }
+        Command::Passwd => {
+            let mut password_array = open_vault(directory, password_source);
+            let master_password = read_master_password(breach_database).unwrap_or_else(|error| {
+                eprintln!("{RED}Error: {error}{RESET}");
+                exit(exitcode::ERROR)
+            });
+            password_array.set_master_password(master_password);
+            save_vault(&mut password_array);
+            println!("The master password was changed");
+        }
+        Command::ClearClipboard { after } => clear_clipboard_after(after),
+        // handled in main like the menu
+        Command::Tui => {}
+        Command::Upgrade { kdf } => {
+            let mut password_array = open_vault(directory, password_source);
+            let old_settings = *password_array.get_settings();
+            let settings = kdf.apply(old_settings).unwrap_or_else(|error| {
+                eprintln!("{RED}Error: {error}{RESET}");
+                exit(exitcode::USAGE)
+            });
+            password_array.set_settings(settings);
+            save_vault(&mut password_array);
+            let settings = password_array.get_settings();
+            if old_settings.version < settings.version {
+                println!(
+                    "Upgraded from format version {} to {}",
+                    old_settings.version, settings.version
+                );
+            }
+            if old_settings.kdf != settings.kdf {
+                println!("KDF: {} -> {}", old_settings.kdf, settings.kdf);
+            }
+            if old_settings == *settings {
+                println!("The vault is already up to date");
+            }
+        }
+    }
+}
+
+/// Shows what changed since the vault was loaded or saved and reverts the chosen changes
+fn review_changes(
+    password_array: &mut PasswordArray,
+    password_array_modified: &mut bool,
+) -> Result<(), Locked> {
+    let changes = password_array.changes();
+    if changes.is_empty() {
+        println!("There are no unsaved changes");
+        return pause();
+    }
+    let mut select = MultiSelect::new(
+        MenuConfig {
+            prompt: String::from("Unsaved changes (choose the ones to revert)"),
+            icon: String::from(">"),
+        },
+        changes.iter().map(|change| change.to_string()).collect(),
+        vec![],
+    );
+    let chosen = select.interact()?;
+    if chosen.is_empty() {
+        return Ok(());
+    }
+    let mut failed = false;
+    for index in chosen {
+        if let Err(error) = password_array.revert(&changes[index]) {
+            println!("{RED}{error}{RESET}");
+            failed = true;
+        }
+    }
+    *password_array_modified = password_array.has_unsaved_changes();
+    if failed {
+        pause()?;
+    }
+    Ok(())
+}
+
+/// Generates a new password under the passwordrules of a service (or the default policy) and
+/// keeps the old one until the user confirms the service accepted the new one
+fn rotate(
+    password_array: &mut PasswordArray,
+    password_array_modified: &mut bool,
+) -> Result<(), Locked> {
+    let Some(service) = pick_service(password_array)? else {
+        return Ok(());
+    };
+    let pending = match password_array.get_pending(&service) {
+        Ok(pending) => pending.map(|p| p.expose_secret().to_string()),
+        Err(error) => {
+            println!("{error}");
+            return pause();
+        }
+    };
+    let new_password = match pending {
+        Some(pending)
+            if !confirm(
+                &format!(
+                    "{service} already has a new password ({pending}), generate a different one? "
+                ),
+                "no",
+            )? =>
+        {
+            pending
+        }
+        _ => {
+            let _ = password_array.set_pending(&service, None);
+            match pending_password(password_array, &service) {
+                Ok(password) => {
+                    *password_array_modified = true;
+                    password
+                }
+                Err(error) => {
+                    println!("{error}");
+                    return pause();
+                }
+            }
+        }
+    };
+    println!("\nChange the password of {service} to: {new_password}");
+    if confirm(&format!("Did {service} accept the new password? "), "no")? {
+        if let Err(error) = password_array.confirm_pending(&service) {
+            println!("{error}");
+            return pause();
+        }
+        *password_array_modified = true;
+        println!("{GREEN}The password of {service} was replaced{RESET}");
+    } else {
+        println!("The old password is kept, rotate {service} again once the new one is accepted");
+    }
+    pause()
+}
+
+/// The pending password of a service, a new one is generated under its passwordrules (or the
+/// default policy) if it doesn't have one
+fn pending_password(password_array: &mut PasswordArray, service: &str) -> Result<String, String> {
+    if let Some(pending) = password_array.get_pending(service)? {
+        return Ok(pending.expose_secret().to_string());
+    }
+    let generator = match password_array.get_password_rules(service)? {
+        Some(rules) => Generator::Rules(rules),
+        None => Generator::Random(PasswordPolicy::default()),
+    };
+    let generated_password = generate_password(&generator)?;
+    password_array.set_pending(
+        service,
+        Some(SecretString::from(generated_password.clone())),
+    )?;
+    Ok(generated_password)
+}
+
+/// Gives every service a pending password and shows them (old and new) until every one is marked
+/// done, the vault is saved after generating and after every service that's done (when the
+/// session is [Locked] it's unlocked where it was)
+fn bulk_rotate(password_array: &mut PasswordArray, services: Vec<String>) -> Result<(), String> {
+    for service in &services {
+        pending_password(password_array, service)?;
+    }
+    password_array.save(false)?;
+    let mut remaining = services;
+    while !remaining.is_empty() {
+        let mut options = vec![];
+        for service in &remaining {
+            options.push(format!(
+                "{service}  old: {}  new: {}",
+                password_array.get_password(service)?.expose_secret(),
+                password_array
+                    .get_pending(service)?
+                    .map_or("", |pending| pending.expose_secret())
+            ));
+        }
+        options.push(String::from("Finish later"));
+        let mut menu = Menu::new(
+            MenuConfig {
+                prompt: String::from("Pick a service once it accepted the new password"),
+                icon: String::from(">"),
+            },
+            options,
+        );
+        let index = loop {
+            match menu.interact() {
+                Ok(index) => break index,
+                Err(Locked) => lock_vault(password_array, true),
+            }
+        };
+        if index == remaining.len() {
+            println!(
+                "{} services still have a pending password, finish them with nspm rotate --pending",
+                remaining.len()
+            );
+            return Ok(());
+        }
+        let service = remaining.remove(index);
+        password_array.confirm_pending(&service)?;
+        password_array.save(false)?;
+        println!("{GREEN}{service} is done{RESET}");
+    }
+    println!("Every service is done");
+    Ok(())
+}
+
+/// Copies `text` to the clipboard and tells (on stderr) when it's cleared, `what` is what was
+/// copied
+fn copy(text: &str, what: &str, clip_timeout: u64) -> Result<(), String> {
+    copy_to_clipboard(text, clip_timeout)?;
+    if clip_timeout == 0 {
+        eprintln!("{what} was copied to the clipboard");
+    } else {
+        eprintln!("{what} was copied to the clipboard, it's cleared in {clip_timeout} seconds");
+    }
+    Ok(())
+}
+
+fn print_import_report(imported: &[String], duplicates: &[(String, String)]) {
+    for service in imported {
+        println!("{GREEN}Imported{RESET} {service}");
+    }
+    for (service, reason) in duplicates {
+        println!("{YELLOW}Skipped{RESET} {service} ({reason})");
+    }
+    println!("{} imported, {} skipped", imported.len(), duplicates.len());
+}
+
+/// The password for `nspm add` and `nspm edit`: generated (and printed), read from stdin when it's
+/// piped or typed in
+fn read_new_password(
+    password_array: &PasswordArray,
+    service: &str,
+    generate: bool,
+    breach_database: Option<&BreachDatabase>,
+) -> Result<SecretString, String> {
+    if generate {
+        let generator = match password_array.get_password_rules(service) {
+            Ok(Some(rules)) => Generator::Rules(rules),
+            _ => Generator::Random(PasswordPolicy::default()),
+        };
+        let password = generate_password(&generator)?;
+        println!("{password}");
+        return Ok(SecretString::from(password));
+    }
+    if io::stdin().is_terminal() {
+        return Ok(without_lock(|| {
+            new_password_input("Password: ", breach_database)
+        }));
+    }
+    read_stdin_password()
+}
+
+/// The master password for `nspm init` and `nspm passwd`: read from stdin when it's piped or typed
+/// in twice
+fn read_master_password(breach_database: Option<&BreachDatabase>) -> Result<SecretString, String> {
+    if !io::stdin().is_terminal() {
+        return read_stdin_password();
+    }
+    let master_password =
+        without_lock(|| new_password_input("New master password: ", breach_database));
+    if master_password.expose_secret().is_empty() {
+        return Err(String::from("The master password can't be empty"));
+    }
+    let repeated = password_input("Repeat the master password: ");
+    eprintln!();
+    if repeated.expose_secret() != master_password.expose_secret() {
+        return Err(String::from("The master passwords don't match"));
+    }
+    Ok(master_password)
+}
+
+/// Reads one line from stdin as a password
+fn read_stdin_password() -> Result<SecretString, String> {
+    let mut password = String::new();
+    io::stdin()
+        .read_line(&mut password)
+        .map_err(|err| format!("Failed to read the password from stdin: {err}"))?;
+    let password = password.trim_end_matches(['\n', '\r']);
+    if password.is_empty() {
+        return Err(String::from("The password from stdin is empty"));
+    }
+    Ok(SecretString::from(password.to_string()))
+}
+
+/// Prints everything about a service, the password is only shown with `reveal`
+fn print_details(
+    password_array: &PasswordArray,
+    service: &str,
+    reveal: bool,
+) -> Result<(), String> {
+    for line in details(password_array, service, reveal)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// [print_details] as JSON (the password and pending password are null without `reveal`)
+fn details_json(
+    password_array: &PasswordArray,
+    service: &str,
+    reveal: bool,
+) -> Result<Value, String> {
+    let password = password_array.get_password(service)?;
+    let pending = password_array.get_pending(service)?;
+    let otp = password_array.get_otp(service).ok().map(|otp| {
+        if otp.remaining().is_some() {
+            "totp"
+        } else {
+            "hotp"
+        }
+    });
+    Ok(json!({
+        "service": service,
+        "password": reveal.then(|| password.expose_secret()),
+        "tags": password_array.get_tags(service)?,
+        "changed": password_array.get_changed(service)?,
+        "expiry": password_array.get_expiry(service)?.map(|expiry| expiry.to_string()),
+        "expires_at": password_array.expires_at(service)?,
+        "password_rules": password_array
+            .get_password_rules(service)?
+            .map(|rules| rules.to_string()),
+        "otp": otp,
+        "pending": pending.is_some(),
+        "pending_password": pending
+            .filter(|_| reveal)
+            .map(|pending| pending.expose_secret()),
+    }))
+}
+
+/// Exits when there's no directory, it isn't a vault or it can't be read
+fn require_directory(directory: &str) {
+    if directory == "\0\0\0\0" {
+        eprintln!(
+            "{RED}Error: This command needs --directory (or a directory in the config){RESET}"
+        );
+        exit(exitcode::USAGE);
+    } else if !fs::exists(directory).unwrap_or(false) {
+        eprintln!("{RED}Error: {directory} doesn't exist{RESET}");
+        exit(exitcode::NOT_FOUND);
+    } else if let Err(error) = check_readable(directory) {
+        eprintln!("{RED}Error: {error}{RESET}");
+        exit(exitcode::IO);
+    } else if !verify_directory(directory) {
+        eprintln!("{RED}Error: {directory} doesn't have the correct structure of a vault{RESET}");
+        exit(exitcode::CORRUPT);
+    }
+}
+
+/// A [FuzzyPicker] for the services of the vault
+fn service_picker(password_array: &PasswordArray) -> FuzzyPicker {
+    let entries = password_array
+        .get_services()
+        .into_iter()
+        .map(|service| {
+            let changed = password_array.get_changed(&service).ok().flatten();
+            (service, changed)
+        })
+        .collect();
+    FuzzyPicker::new(
+        MenuConfig {
+            prompt: String::from("Service: "),
+            icon: String::from(">"),
+        },
+        entries,
+    )
+}
+
+/// Lets the user search for a service, None if there are no services or the search was cancelled
+fn pick_service(password_array: &PasswordArray) -> Result<Option<String>, Locked> {
+    if password_array.get_services().is_empty() {
+        println!("There are no passwords");
+        pause()?;
+        return Ok(None);
+    }
+    service_picker(password_array).interact(String::new())
+}
+
+/// Exits when the vault doesn't have `service`
+fn require_service(password_array: &PasswordArray, service: &str) {
+    if !password_array.get_services().iter().any(|s| s == service) {
+        eprintln!("{RED}Error: couldn't find service {service}{RESET}");
+        exit(exitcode::NOT_FOUND);
+    }
+}
+
+/// Gets the master password (from `password_source` or the user) and loads the vault without the progress bar
+fn open_vault(directory: &str, password_source: Option<&PasswordSource>) -> PasswordArray {
+    require_directory(directory);
+    if fs::exists(format!("{directory}_tmp")).unwrap_or(false) {
+        eprintln!(
+            "{RED}Error: {directory} is locked, another nspm is saving it (remove {directory}_tmp if nspm crashed){RESET}"
+        );
+        exit(exitcode::LOCKED);
+    }
+    let master_password = master_password(directory, password_source);
+    let mut password_array = PasswordArray::new(master_password, directory.to_string());
+    if let Err(error) = password_array.load(false) {
+        eprintln!("{error}");
+        exit(exitcode::CORRUPT)
+    }
+    print_expiry_warnings(&password_array);
+    password_array
+}
+
+/// Saves the vault after a subcommand changed it, exits when that fails
+fn save_vault(password_array: &mut PasswordArray) {
+    if let Err(error) = password_array.save(false) {
+        eprintln!("{RED}Error: {error}{RESET}");
+        exit(exitcode::IO)
+    }
+}
+
+/// Warns (on stderr) about expired passwords and passwords that expire soon, returns if there
+/// were any
+fn print_expiry_warnings(password_array: &PasswordArray) -> bool {
+    let due = rotation::due(password_array);
+    for (service, expires_at) in &due {
+        eprintln!(
+            "{YELLOW}Warning:{RESET} the password of {service} {}",
+            rotation::describe(*expires_at)
+        );
+    }
+    !due.is_empty()
 }
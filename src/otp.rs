@@ -0,0 +1,645 @@
+use crate::storage::unix_time;
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use hmac::{Hmac, Mac, digest::KeyInit};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use std::fmt::Display;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+/// The hash function used for the HMAC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Result<Algorithm, String> {
+        match name.to_uppercase().as_str() {
+            "SHA1" => Ok(Algorithm::Sha1),
+            "SHA256" => Ok(Algorithm::Sha256),
+            "SHA512" => Ok(Algorithm::Sha512),
+            other => Err(format!("Unsupported algorithm: {other}")),
+        }
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Algorithm::Sha1 => write!(f, "SHA1"),
+            Algorithm::Sha256 => write!(f, "SHA256"),
+            Algorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+/// Time based (RFC 6238) or counter based (RFC 4226)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpKind {
+    Totp { period: u64 },
+    Hotp { counter: u64 },
+}
+
+/// A one time password generator, usually made from an `otpauth://` URI
+#[derive(Debug, Clone)]
+pub struct Otp {
+    pub kind: OtpKind,
+    pub secret: Vec<u8>,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    pub issuer: String,
+    pub account: String,
+}
+
+impl Otp {
+    /// Makes a TOTP with the defaults most sites use (SHA1, 6 digits, 30 seconds) from a base32
+    /// secret
+    pub fn from_base32(secret: &str, issuer: String, account: String) -> Result<Otp, String> {
+        Ok(Otp {
+            kind: OtpKind::Totp {
+                period: DEFAULT_PERIOD,
+            },
+            secret: base32_decode(secret)?,
+            algorithm: Algorithm::Sha1,
+            digits: DEFAULT_DIGITS,
+            issuer,
+            account,
+        })
+    }
+    /// Parses an `otpauth://TYPE/LABEL?PARAMETERS` URI
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::otp::{Otp, OtpKind};
+    /// let otp = Otp::from_uri("otpauth://totp/ACME%20Co:john@example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&digits=8").unwrap();
+    /// assert_eq!(otp.issuer, "ACME Co");
+    /// assert_eq!(otp.account, "john@example.com");
+    /// assert_eq!(otp.digits, 8);
+    /// assert_eq!(otp.kind, OtpKind::Totp { period: 30 });
+    /// ```
+    pub fn from_uri(uri: &str) -> Result<Otp, String> {
+        let rest = uri
+            .trim()
+            .strip_prefix("otpauth://")
+            .ok_or("URI doesn't start with otpauth://")?;
+        let (kind, rest) = rest.split_once('/').ok_or("URI has no label")?;
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let label = percent_decode(label)?;
+        let (mut issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (issuer.trim().to_string(), account.trim().to_string()),
+            None => (String::new(), label.trim().to_string()),
+        };
+        let (mut secret, mut algorithm, mut digits, mut period, mut counter) =
+            (None, Algorithm::Sha1, DEFAULT_DIGITS, DEFAULT_PERIOD, None);
+        for parameter in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let value = percent_decode(value)?;
+            match key.to_lowercase().as_str() {
+                "secret" => secret = Some(base32_decode(&value)?),
+                "issuer" => issuer = value,
+                "algorithm" => algorithm = Algorithm::parse(&value)?,
+                "digits" => {
+                    digits = value
+                        .parse()
+                        .map_err(|err| format!("Invalid digits: {err}"))?
+                }
+                "period" => {
+                    period = value
+                        .parse()
+                        .map_err(|err| format!("Invalid period: {err}"))?
+                }
+                "counter" => {
+                    counter = Some(
+                        value
+                            .parse()
+                            .map_err(|err| format!("Invalid counter: {err}"))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+        if !(6..=10).contains(&digits) {
+            return Err(format!("Invalid amount of digits: {digits}"));
+        }
+        if period == 0 {
+            return Err(String::from("Period can't be 0"));
+        }
+        let kind = match kind.to_lowercase().as_str() {
+            "totp" => OtpKind::Totp { period },
+            "hotp" => OtpKind::Hotp {
+                counter: counter.ok_or("HOTP URI has no counter")?,
+            },
+            other => return Err(format!("Unknown OTP type: {other}")),
+        };
+        Ok(Otp {
+            kind,
+            secret: secret.ok_or("URI has no secret")?,
+            algorithm,
+            digits,
+            issuer,
+            account,
+        })
+    }
+    /// Decodes every account in a Google Authenticator export
+    /// (`otpauth-migration://offline?data=...`), the data is a base64 protobuf `MigrationPayload`
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::otp::{Algorithm, Otp, OtpKind};
+    /// let otps = Otp::from_migration_uri("otpauth-migration://offline?data=ChkKBUhlbGxvEgZqb2huQGEaBEFDTUUgATAC").unwrap();
+    /// assert_eq!(otps[0].secret, b"Hello");
+    /// assert_eq!(otps[0].issuer, "ACME");
+    /// assert_eq!(otps[0].account, "john@a");
+    /// assert_eq!(otps[0].kind, OtpKind::Totp { period: 30 });
+    /// ```
+    pub fn from_migration_uri(uri: &str) -> Result<Vec<Otp>, String> {
+        let query = uri
+            .trim()
+            .strip_prefix("otpauth-migration://offline?")
+            .ok_or("URI doesn't start with otpauth-migration://offline?")?;
+        let data = query
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("data="))
+            .ok_or("URI has no data")?;
+        // '+' is part of base64 here, not an encoded space
+        let data = percent_decode(&data.replace('+', "%2B"))?;
+        let payload = STANDARD
+            .decode(data.trim())
+            .map_err(|err| format!("Failed to decode from base64: {err}"))?;
+        let mut otps = Vec::new();
+        let mut reader = ProtobufReader::new(&payload);
+        while let Some((field, value)) = reader.field()? {
+            if let (1, ProtobufValue::Bytes(parameters)) = (field, value) {
+                otps.push(Otp::from_migration_parameters(parameters)?);
+            }
+        }
+        Ok(otps)
+    }
+    /// Parses either kind of URI, `otpauth-migration://` URIs can have multiple accounts
+    pub fn import(uri: &str) -> Result<Vec<Otp>, String> {
+        if uri.trim().starts_with("otpauth-migration://") {
+            Otp::from_migration_uri(uri)
+        } else {
+            Ok(vec![Otp::from_uri(uri)?])
+        }
+    }
+    fn from_migration_parameters(bytes: &[u8]) -> Result<Otp, String> {
+        let (mut secret, mut name, mut issuer) = (Vec::new(), String::new(), String::new());
+        let (mut algorithm, mut digits, mut is_hotp, mut counter) =
+            (Algorithm::Sha1, DEFAULT_DIGITS, false, 0);
+        let mut reader = ProtobufReader::new(bytes);
+        while let Some((field, value)) = reader.field()? {
+            match (field, value) {
+                (1, ProtobufValue::Bytes(b)) => secret = b.to_vec(),
+                (2, ProtobufValue::Bytes(b)) => name = String::from_utf8_lossy(b).to_string(),
+                (3, ProtobufValue::Bytes(b)) => issuer = String::from_utf8_lossy(b).to_string(),
+                (4, ProtobufValue::Varint(v)) => {
+                    algorithm = match v {
+                        0 | 1 => Algorithm::Sha1,
+                        2 => Algorithm::Sha256,
+                        3 => Algorithm::Sha512,
+                        _ => return Err(format!("{name} uses an unsupported algorithm (MD5)")),
+                    }
+                }
+                (5, ProtobufValue::Varint(v)) => digits = if v == 2 { 8 } else { 6 },
+                (6, ProtobufValue::Varint(v)) => is_hotp = v == 1,
+                (7, ProtobufValue::Varint(v)) => counter = v,
+                _ => {}
+            }
+        }
+        if secret.is_empty() {
+            return Err(format!("{name} has no secret"));
+        }
+        // the name is usually "issuer:account" like an otpauth label
+        let account = match name.split_once(':') {
+            Some((prefix, account)) if issuer.is_empty() || prefix.trim() == issuer => {
+                if issuer.is_empty() {
+                    issuer = prefix.trim().to_string();
+                }
+                account.trim().to_string()
+            }
+            _ => name,
+        };
+        Ok(Otp {
+            kind: if is_hotp {
+                OtpKind::Hotp { counter }
+            } else {
+                OtpKind::Totp {
+                    period: DEFAULT_PERIOD,
+                }
+            },
+            secret,
+            algorithm,
+            digits,
+            issuer,
+            account,
+        })
+    }
+    /// A service name for the [Otp] like "issuer (account)"
+    pub fn service_name(&self) -> String {
+        match (self.issuer.is_empty(), self.account.is_empty()) {
+            (false, false) => format!("{} ({})", self.issuer, self.account),
+            (false, true) => self.issuer.clone(),
+            _ => self.account.clone(),
+        }
+    }
+    /// Turns the [Otp] back into an `otpauth://` URI that [from_uri][Otp::from_uri] can parse
+    pub fn to_uri(&self) -> String {
+        let label = if self.issuer.is_empty() {
+            percent_encode(&self.account)
+        } else {
+            format!(
+                "{}:{}",
+                percent_encode(&self.issuer),
+                percent_encode(&self.account)
+            )
+        };
+        let (kind, parameter) = match self.kind {
+            OtpKind::Totp { period } => ("totp", format!("period={period}")),
+            OtpKind::Hotp { counter } => ("hotp", format!("counter={counter}")),
+        };
+        let mut uri = format!(
+            "otpauth://{kind}/{label}?secret={}&algorithm={}&digits={}&{parameter}",
+            base32_encode(&self.secret),
+            self.algorithm,
+            self.digits
+        );
+        if !self.issuer.is_empty() {
+            uri.push_str(&format!("&issuer={}", percent_encode(&self.issuer)));
+        }
+        uri
+    }
+    /// Generates the current code, for HOTP this uses the counter and then increments it so the
+    /// caller has to save the [Otp] again
+    pub fn generate(&mut self) -> String {
+        match &mut self.kind {
+            OtpKind::Totp { period } => {
+                let period = *period;
+                self.totp(unix_time(), period)
+            }
+            OtpKind::Hotp { counter } => {
+                let current = *counter;
+                *counter += 1;
+                self.hotp(current)
+            }
+        }
+    }
+    /// Seconds until the current TOTP code changes (None for HOTP)
+    pub fn remaining(&self) -> Option<u64> {
+        match self.kind {
+            OtpKind::Totp { period } => Some(period - unix_time() % period),
+            OtpKind::Hotp { .. } => None,
+        }
+    }
+    /// HOTP value for `counter` (RFC 4226)
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::otp::{Algorithm, Otp, OtpKind};
+    /// let otp = Otp {
+    ///     kind: OtpKind::Hotp { counter: 0 },
+    ///     secret: b"12345678901234567890".to_vec(),
+    ///     algorithm: Algorithm::Sha1,
+    ///     digits: 6,
+    ///     issuer: String::new(),
+    ///     account: String::new(),
+    /// };
+    /// assert_eq!(otp.hotp(0), "755224");
+    /// assert_eq!(otp.hotp(1), "287082");
+    /// assert_eq!(otp.hotp(9), "520489");
+    /// ```
+    pub fn hotp(&self, counter: u64) -> String {
+        let message = counter.to_be_bytes();
+        let hash = match self.algorithm {
+            Algorithm::Sha1 => hmac::<Hmac<Sha1>>(&self.secret, &message),
+            Algorithm::Sha256 => hmac::<Hmac<Sha256>>(&self.secret, &message),
+            Algorithm::Sha512 => hmac::<Hmac<Sha512>>(&self.secret, &message),
+        };
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+        let code = binary as u64 % 10_u64.pow(self.digits);
+        format!("{code:0width$}", width = self.digits as usize)
+    }
+    /// TOTP value at `time` seconds since the unix epoch (RFC 6238)
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::otp::{Algorithm, Otp, OtpKind};
+    /// let otp = Otp {
+    ///     kind: OtpKind::Totp { period: 30 },
+    ///     secret: b"12345678901234567890".to_vec(),
+    ///     algorithm: Algorithm::Sha1,
+    ///     digits: 8,
+    ///     issuer: String::new(),
+    ///     account: String::new(),
+    /// };
+    /// assert_eq!(otp.totp(59, 30), "94287082");
+    /// assert_eq!(otp.totp(1111111109, 30), "07081804");
+    /// assert_eq!(otp.totp(20000000000, 30), "65353130");
+    /// ```
+    pub fn totp(&self, time: u64, period: u64) -> String {
+        self.hotp(time / period)
+    }
+}
+
+enum ProtobufValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Just enough protobuf to read the Google Authenticator export
+struct ProtobufReader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> ProtobufReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, index: 0 }
+    }
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut result = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .bytes
+                .get(self.index)
+                .ok_or("Protobuf data ended in the middle of a number")?;
+            self.index += 1;
+            result |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        Err(String::from("Protobuf number is too long"))
+    }
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .index
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or("Protobuf data ended in the middle of a field")?;
+        let bytes = &self.bytes[self.index..end];
+        self.index = end;
+        Ok(bytes)
+    }
+    fn field(&mut self) -> Result<Option<(u64, ProtobufValue<'a>)>, String> {
+        if self.index >= self.bytes.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => ProtobufValue::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                ProtobufValue::Fixed
+            }
+            2 => {
+                let length = self.varint()? as usize;
+                ProtobufValue::Bytes(self.take(length)?)
+            }
+            5 => {
+                self.take(4)?;
+                ProtobufValue::Fixed
+            }
+            wire_type => return Err(format!("Unsupported protobuf wire type: {wire_type}")),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+fn hmac<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC can take keys of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Decodes RFC 4648 base32, ignoring case, spaces, dashes and padding
+///
+/// # Example
+/// ```
+/// use nspm::otp::base32_decode;
+/// assert_eq!(base32_decode("JBSW Y3DP").unwrap(), b"Hello");
+/// ```
+pub fn base32_decode(string: &str) -> Result<Vec<u8>, String> {
+    let mut result = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for character in string.chars() {
+        if [' ', '-', '='].contains(&character) {
+            continue;
+        }
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|c| *c as char == character.to_ascii_uppercase())
+            .ok_or(format!("Invalid base32 character: {character}"))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if result.is_empty() {
+        return Err(String::from("Secret is empty"));
+    }
+    Ok(result)
+}
+
+/// Encodes bytes as unpadded base32
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    result
+}
+
+fn percent_decode(string: &str) -> Result<String, String> {
+    let bytes = string.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if index + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[index + 1..index + 3])
+                    .map_err(|err| format!("Invalid percent encoding: {err}"))?;
+                result.push(
+                    u8::from_str_radix(hex, 16)
+                        .map_err(|err| format!("Invalid percent encoding: {err}"))?,
+                );
+                index += 3;
+            }
+            b'+' => {
+                result.push(b' ');
+                index += 1;
+            }
+            byte => {
+                result.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(result).map_err(|err| format!("URI isn't valid UTF-8: {err}"))
+}
+
+fn percent_encode(string: &str) -> String {
+    let mut result = String::new();
+    for byte in string.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~@".contains(&byte) {
+            result.push(byte as char);
+        } else {
+            result.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn otp(algorithm: Algorithm, secret: &[u8], digits: u32) -> Otp {
+        Otp {
+            kind: OtpKind::Totp { period: 30 },
+            secret: secret.to_vec(),
+            algorithm,
+            digits,
+            issuer: String::new(),
+            account: String::new(),
+        }
+    }
+
+    #[test]
+    fn hotp_rfc4226_vectors() {
+        let otp = otp(Algorithm::Sha1, b"12345678901234567890", 6);
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(otp.hotp(counter as u64), *code, "counter {counter}");
+        }
+    }
+
+    #[test]
+    fn totp_rfc6238_vectors() {
+        let sha1 = otp(Algorithm::Sha1, b"12345678901234567890", 8);
+        let sha256 = otp(Algorithm::Sha256, b"12345678901234567890123456789012", 8);
+        let sha512 = otp(
+            Algorithm::Sha512,
+            b"1234567890123456789012345678901234567890123456789012345678901234",
+            8,
+        );
+        let expected = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, sha1_code, sha256_code, sha512_code) in expected {
+            assert_eq!(sha1.totp(time, 30), sha1_code, "SHA1 at {time}");
+            assert_eq!(sha256.totp(time, 30), sha256_code, "SHA256 at {time}");
+            assert_eq!(sha512.totp(time, 30), sha512_code, "SHA512 at {time}");
+        }
+    }
+
+    #[test]
+    fn generate_increments_hotp_counter() {
+        let mut otp = otp(Algorithm::Sha1, b"12345678901234567890", 6);
+        otp.kind = OtpKind::Hotp { counter: 1 };
+        assert_eq!(otp.generate(), "287082");
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 2 });
+        assert_eq!(otp.generate(), "359152");
+    }
+
+    #[test]
+    fn from_uri() {
+        let otp = Otp::from_uri("otpauth://totp/ACME%20Co:john@example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&digits=8").unwrap();
+        assert_eq!(otp.issuer, "ACME Co");
+        assert_eq!(otp.account, "john@example.com");
+        assert_eq!(otp.digits, 8);
+        assert_eq!(otp.kind, OtpKind::Totp { period: 30 });
+        assert_eq!(otp.algorithm, Algorithm::Sha1);
+
+        let otp =
+            Otp::from_uri("otpauth://hotp/a?secret=JBSWY3DP&counter=7&algorithm=sha256").unwrap();
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 7 });
+        assert_eq!(otp.algorithm, Algorithm::Sha256);
+        assert_eq!(otp.account, "a");
+        assert_eq!(otp.issuer, "");
+    }
+
+    #[test]
+    fn from_uri_errors() {
+        assert!(Otp::from_uri("https://example.com").is_err());
+        assert!(Otp::from_uri("otpauth://totp/a").is_err());
+        assert!(Otp::from_uri("otpauth://hotp/a?secret=JBSWY3DP").is_err());
+        assert!(Otp::from_uri("otpauth://totp/a?secret=JBSWY3DP&digits=5").is_err());
+        assert!(Otp::from_uri("otpauth://totp/a?secret=JBSWY3DP&period=0").is_err());
+        assert!(Otp::from_uri("otpauth://totp/a?secret=JBSWY3DP&algorithm=MD5").is_err());
+    }
+
+    #[test]
+    fn to_uri_round_trips() {
+        let mut otp = otp(Algorithm::Sha512, b"Hello!", 8);
+        otp.kind = OtpKind::Hotp { counter: 42 };
+        otp.issuer = String::from("ACME Co");
+        otp.account = String::from("john@example.com");
+        let parsed = Otp::from_uri(&otp.to_uri()).unwrap();
+        assert_eq!(parsed.kind, otp.kind);
+        assert_eq!(parsed.secret, otp.secret);
+        assert_eq!(parsed.algorithm, otp.algorithm);
+        assert_eq!(parsed.digits, otp.digits);
+        assert_eq!(parsed.issuer, otp.issuer);
+        assert_eq!(parsed.account, otp.account);
+    }
+
+    #[test]
+    fn from_migration_uri() {
+        let otps = Otp::from_migration_uri(
+            "otpauth-migration://offline?data=ChkKBUhlbGxvEgZqb2huQGEaBEFDTUUgATAC",
+        )
+        .unwrap();
+        assert_eq!(otps.len(), 1);
+        assert_eq!(otps[0].secret, b"Hello");
+        assert_eq!(otps[0].issuer, "ACME");
+        assert_eq!(otps[0].account, "john@a");
+        assert_eq!(otps[0].kind, OtpKind::Totp { period: 30 });
+    }
+
+    #[test]
+    fn base32() {
+        assert_eq!(base32_decode("JBSW Y3DP").unwrap(), b"Hello");
+        assert_eq!(base32_decode("jbsw-y3dp====").unwrap(), b"Hello");
+        assert!(base32_decode("JBSW1").is_err());
+        assert!(base32_decode("").is_err());
+        for bytes in [&b"f"[..], b"fo", b"foo", b"foob", b"fooba", b"foobar"] {
+            assert_eq!(base32_decode(&base32_encode(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    }
+}
@@ -0,0 +1,292 @@
+use crate::generator::{CharClass, PasswordPolicy, generate_from};
+use std::fmt::Display;
+
+/// A parsed [passwordrules](https://developer.apple.com/password-rules/) string like
+/// `required: lower; required: digit; allowed: [-_]; max-consecutive: 2; minlength: 12`
+#[derive(Debug, Clone)]
+pub struct PasswordRules {
+    source: String,
+    /// Every set needs at least one character in the password
+    pub required: Vec<Vec<char>>,
+    /// Characters that can be used (includes the required ones)
+    pub allowed: Vec<char>,
+    pub max_consecutive: Option<usize>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+}
+
+impl Display for PasswordRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl PasswordRules {
+    /// Parses a passwordrules string, unknown properties are ignored like browsers do
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::passwordrules::PasswordRules;
+    /// let rules = PasswordRules::parse("required: lower; required: digit; allowed: [-_]; max-consecutive: 2; minlength: 12").unwrap();
+    /// assert_eq!(rules.required.len(), 2);
+    /// assert_eq!(rules.allowed.len(), 26 + 10 + 2);
+    /// assert_eq!(rules.max_consecutive, Some(2));
+    /// assert_eq!(rules.min_length, Some(12));
+    /// ```
+    pub fn parse(source: &str) -> Result<PasswordRules, String> {
+        let mut rules = PasswordRules {
+            source: source.trim().to_string(),
+            required: vec![],
+            allowed: vec![],
+            max_consecutive: None,
+            min_length: None,
+            max_length: None,
+        };
+        for rule in split_rules(source) {
+            let (name, value) = rule
+                .split_once(':')
+                .ok_or(format!("Rule doesn't have a value: {rule}"))?;
+            match name.trim().to_lowercase().as_str() {
+                "required" => {
+                    let set = parse_classes(value)?;
+                    add_to(&mut rules.allowed, &set);
+                    rules.required.push(set);
+                }
+                "allowed" => {
+                    let set = parse_classes(value)?;
+                    add_to(&mut rules.allowed, &set);
+                }
+                "max-consecutive" => rules.max_consecutive = Some(parse_number(value)?),
+                "minlength" => rules.min_length = Some(parse_number(value)?),
+                "maxlength" => rules.max_length = Some(parse_number(value)?),
+                _ => {}
+            }
+        }
+        if rules.allowed.is_empty() {
+            rules.allowed = ascii_printable();
+        }
+        if let (Some(min), Some(max)) = (rules.min_length, rules.max_length)
+            && min > max
+        {
+            return Err(format!(
+                "minlength ({min}) is bigger than maxlength ({max})"
+            ));
+        }
+        if rules.max_consecutive == Some(0) {
+            return Err(String::from("max-consecutive can't be 0"));
+        }
+        Ok(rules)
+    }
+    /// The length generated passwords get, the default length clamped to minlength and maxlength
+    pub fn length(&self) -> usize {
+        let mut length = PasswordPolicy::default().length;
+        if let Some(min) = self.min_length {
+            length = length.max(min);
+        }
+        if let Some(max) = self.max_length {
+            length = length.min(max);
+        }
+        length
+    }
+    /// Generates a password that follows the rules
+    pub fn generate(&self) -> Result<String, String> {
+        let required: Vec<(Vec<char>, usize)> =
+            self.required.iter().map(|set| (set.clone(), 1)).collect();
+        generate_from(self.length(), &required, &self.allowed, |password| {
+            self.max_consecutive
+                .is_none_or(|max| password.chunk_by(|a, b| a == b).all(|run| run.len() <= max))
+        })
+    }
+    /// length * log2(amount of allowed characters)
+    pub fn entropy(&self) -> f64 {
+        self.length() as f64 * (self.allowed.len() as f64).log2()
+    }
+}
+
+/// Splits on ';' but not inside a custom character class like `[;]`
+fn split_rules(source: &str) -> Vec<String> {
+    let (mut rules, mut buffer, mut in_brackets) = (vec![], String::new(), false);
+    let characters: Vec<char> = source.chars().collect();
+    for (index, character) in characters.iter().enumerate() {
+        match character {
+            '[' if !in_brackets => in_brackets = true,
+            ']' if in_brackets && closes_class(&characters[index + 1..]) => in_brackets = false,
+            ';' if !in_brackets => {
+                rules.push(std::mem::take(&mut buffer));
+                continue;
+            }
+            _ => {}
+        }
+        buffer.push(*character);
+    }
+    rules.push(buffer);
+    rules
+        .into_iter()
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+/// A ']' ends a custom class when the next thing after it is ',', ';' or the end
+fn closes_class(rest: &[char]) -> bool {
+    rest.iter()
+        .find(|c| !c.is_whitespace())
+        .is_none_or(|c| [',', ';'].contains(c))
+}
+
+fn parse_classes(value: &str) -> Result<Vec<char>, String> {
+    let mut set = vec![];
+    let characters: Vec<char> = value.chars().collect();
+    let mut index = 0;
+    while index < characters.len() {
+        let character = characters[index];
+        if character.is_whitespace() || character == ',' {
+            index += 1;
+        } else if character == '[' {
+            let mut end = index + 1;
+            while end < characters.len()
+                && !(characters[end] == ']' && closes_class(&characters[end + 1..]))
+            {
+                end += 1;
+            }
+            if end >= characters.len() {
+                return Err(format!("Unclosed character class: {value}"));
+            }
+            add_to(&mut set, &characters[index + 1..end]);
+            index = end + 1;
+        } else {
+            let name: String = characters[index..]
+                .iter()
+                .take_while(|c| !c.is_whitespace() && **c != ',')
+                .collect();
+            index += name.chars().count();
+            let class = match name.to_lowercase().as_str() {
+                "upper" => CharClass::Uppercase.chars().chars().collect(),
+                "lower" => CharClass::Lowercase.chars().chars().collect(),
+                "digit" => CharClass::Digits.chars().chars().collect(),
+                "special" => CharClass::Symbols.chars().chars().collect(),
+                // generated passwords stay in ascii even when unicode is allowed
+                "ascii-printable" | "unicode" => ascii_printable(),
+                other => return Err(format!("Unknown character class: {other}")),
+            };
+            add_to(&mut set, &class);
+        }
+    }
+    if set.is_empty() {
+        return Err(format!("Rule doesn't have any characters: {value}"));
+    }
+    Ok(set)
+}
+
+fn parse_number(value: &str) -> Result<usize, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|err| format!("Invalid number {}: {err}", value.trim()))
+}
+
+/// Every printable ASCII character (0x20 to 0x7e), the space too
+fn ascii_printable() -> Vec<char> {
+    CharClass::ALL
+        .iter()
+        .flat_map(|class| class.chars().chars())
+        .chain([' '])
+        .collect()
+}
+
+fn add_to(set: &mut Vec<char>, characters: &[char]) {
+    for character in characters {
+        if !set.contains(character) {
+            set.push(*character);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let rules = PasswordRules::parse(
+            "required: lower; required: digit; allowed: [-_]; max-consecutive: 2; minlength: 12",
+        )
+        .unwrap();
+        assert_eq!(rules.required.len(), 2);
+        assert_eq!(rules.allowed.len(), 26 + 10 + 2);
+        assert!(rules.allowed.contains(&'-') && rules.allowed.contains(&'_'));
+        assert_eq!(rules.max_consecutive, Some(2));
+        assert_eq!(rules.min_length, Some(12));
+        assert_eq!(rules.max_length, None);
+        assert_eq!(
+            rules.to_string(),
+            "required: lower; required: digit; allowed: [-_]; max-consecutive: 2; minlength: 12"
+        );
+    }
+
+    #[test]
+    fn parse_custom_classes() {
+        let rules =
+            PasswordRules::parse("required: [;], upper; allowed: [a]]; unknown: 3").unwrap();
+        let mut required = vec![';'];
+        required.extend(CharClass::Uppercase.chars().chars());
+        assert_eq!(rules.required, [required]);
+        assert!(rules.allowed.contains(&'a') && rules.allowed.contains(&']'));
+        assert_eq!(split_rules("allowed: [;]; minlength: 3").len(), 2);
+    }
+
+    #[test]
+    fn parse_defaults_to_ascii_printable() {
+        let rules = PasswordRules::parse("minlength: 20").unwrap();
+        assert_eq!(rules.allowed.len(), 26 + 26 + 10 + 32 + 1);
+        assert!(rules.allowed.contains(&' '));
+        assert!(rules.required.is_empty());
+        assert_eq!(rules.length(), 20);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(PasswordRules::parse("required lower").is_err());
+        assert!(PasswordRules::parse("required: emoji").is_err());
+        assert!(PasswordRules::parse("allowed: [abc").is_err());
+        assert!(PasswordRules::parse("required: ").is_err());
+        assert!(PasswordRules::parse("minlength: ten").is_err());
+        assert!(PasswordRules::parse("minlength: 20; maxlength: 10").is_err());
+        assert!(PasswordRules::parse("max-consecutive: 0").is_err());
+    }
+
+    #[test]
+    fn length_is_clamped() {
+        let default = PasswordPolicy::default().length;
+        assert_eq!(PasswordRules::parse("").unwrap().length(), default);
+        assert_eq!(PasswordRules::parse("maxlength: 8").unwrap().length(), 8);
+        assert_eq!(
+            PasswordRules::parse("minlength: 30; maxlength: 40")
+                .unwrap()
+                .length(),
+            30
+        );
+    }
+
+    #[test]
+    fn generate_follows_the_rules() {
+        let rules = PasswordRules::parse(
+            "required: digit; required: [-]; allowed: lower; max-consecutive: 1; maxlength: 10",
+        )
+        .unwrap();
+        for _ in 0..100 {
+            let password: Vec<char> = rules.generate().unwrap().chars().collect();
+            assert_eq!(password.len(), 10);
+            assert!(password.iter().any(|c| c.is_ascii_digit()));
+            assert!(password.contains(&'-'));
+            assert!(password.iter().all(|c| rules.allowed.contains(c)));
+            assert!(password.windows(2).all(|w| w[0] != w[1]));
+        }
+    }
+
+    #[test]
+    fn entropy() {
+        let rules = PasswordRules::parse("allowed: digit; minlength: 16; maxlength: 16").unwrap();
+        assert!((rules.entropy() - 16.0 * 10_f64.log2()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,92 @@
+//! Where the master password comes from when nobody is there to type it in
+//!
+//! None of these are as safe as typing the password in, every one has its own risks:
+//! - `--password-fd N`: the password is read from an open file descriptor (like
+//!   `nspm --password-fd 3 3< <(pass show nspm)`), it never touches the disk, the environment or
+//!   the command line so this is the safest one
+//! - `--password-file PATH`: anyone (and any backup) that can read the file can read the
+//!   password, keep it readable only by you (`chmod 600`) and off shared or synced disks
+//! - `--password-env VAR`: every process started by nspm inherits the environment, other
+//!   processes of the same user can read it (`/proc/PID/environ`) and `VAR=... nspm` ends up in
+//!   the shell history
+//! - `--password-command CMD`: the command runs through `sh -c` with your privileges and whatever
+//!   it prints is trusted as the password, the command line itself can be seen in `ps`
+
+use secrecy::SecretString;
+use std::{
+    env, fs,
+    process::{Command, Stdio},
+};
+
+/// A non-interactive source of the master password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordSource {
+    /// An open file descriptor
+    Fd(u32),
+    File(String),
+    /// The name of an environment variable
+    Env(String),
+    /// A shell command that prints the password
+    Command(String),
+}
+
+impl PasswordSource {
+    /// Reads the password, only the first line counts (without the newline)
+    pub fn read(&self) -> Result<SecretString, String> {
+        let contents = match self {
+            PasswordSource::Fd(fd) => fs::read_to_string(format!("/dev/fd/{fd}"))
+                .map_err(|err| format!("Failed to read file descriptor {fd}, Error: {err}"))?,
+            PasswordSource::File(path) => {
+                warn_if_readable_by_others(path);
+                fs::read_to_string(path)
+                    .map_err(|err| format!("Failed to read {path}, Error: {err}"))?
+            }
+            PasswordSource::Env(variable) => env::var(variable)
+                .map_err(|err| format!("Failed to read ${variable}, Error: {err}"))?,
+            PasswordSource::Command(command) => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .map_err(|err| format!("Failed to run {command}, Error: {err}"))?;
+                if !output.status.success() {
+                    return Err(format!("{command} failed ({})", output.status));
+                }
+                String::from_utf8(output.stdout)
+                    .map_err(|_| format!("{command} didn't print valid UTF-8"))?
+            }
+        };
+        let password = contents.lines().next().unwrap_or_default();
+        if password.is_empty() {
+            return Err(format!("The master password from {self} is empty"));
+        }
+        Ok(SecretString::from(password.to_string()))
+    }
+}
+
+impl std::fmt::Display for PasswordSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordSource::Fd(fd) => write!(f, "file descriptor {fd}"),
+            PasswordSource::File(path) => write!(f, "{path}"),
+            PasswordSource::Env(variable) => write!(f, "${variable}"),
+            PasswordSource::Command(command) => write!(f, "{command}"),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn warn_if_readable_by_others(path: &str) {
+    use crate::ansi::constants::{RESET, YELLOW};
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = fs::metadata(path)
+        && metadata.permissions().mode() & 0o077 != 0
+    {
+        eprintln!("{YELLOW}Warning:{RESET} {path} can be read by other users, chmod 600 it");
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_readable_by_others(_path: &str) {}
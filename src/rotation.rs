@@ -0,0 +1,206 @@
+//! Expiry dates and rotation intervals of passwords
+
+use crate::storage::{PasswordArray, unix_time};
+use std::fmt::Display;
+
+pub const SECONDS_PER_DAY: u64 = 86400;
+/// Passwords expiring in this many days get a warning too
+pub const EXPIRING_SOON_DAYS: u64 = 14;
+
+/// When a password has to be changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// A fixed date (unix time), it's removed when the password changes
+    Date(u64),
+    /// Every this many days after the last change
+    Every(u64),
+}
+
+impl Display for Expiry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expiry::Date(date) => write!(f, "{}", format_date(*date)),
+            Expiry::Every(days) => write!(f, "{days}d"),
+        }
+    }
+}
+
+impl Expiry {
+    /// Parses a date like `2025-12-31` or an interval like `90d`
+    ///
+    /// # Example
+    /// ```
+    /// use nspm::rotation::Expiry;
+    /// assert_eq!(Expiry::parse("90d"), Ok(Expiry::Every(90)));
+    /// assert_eq!(Expiry::parse("2001-09-09"), Ok(Expiry::Date(999993600)));
+    /// ```
+    pub fn parse(value: &str) -> Result<Expiry, String> {
+        let value = value.trim();
+        if let Some(days) = value.strip_suffix('d') {
+            return match days.parse() {
+                Ok(0) => Err(String::from("The rotation interval can't be 0 days")),
+                Ok(days) => Ok(Expiry::Every(days)),
+                Err(err) => Err(format!("Invalid rotation interval {value}: {err}")),
+            };
+        }
+        parse_date(value).map(Expiry::Date)
+    }
+    /// When the password expires, `changed` is when it was last changed
+    pub fn expires_at(&self, changed: u64) -> u64 {
+        match self {
+            Expiry::Date(date) => *date,
+            Expiry::Every(days) => changed + days * SECONDS_PER_DAY,
+        }
+    }
+}
+
+/// Services that are expired or expire in [EXPIRING_SOON_DAYS] with when they expire, the ones
+/// that expire first come first
+pub fn due(password_array: &PasswordArray) -> Vec<(String, u64)> {
+    let soon = unix_time() + EXPIRING_SOON_DAYS * SECONDS_PER_DAY;
+    let mut due: Vec<(String, u64)> = password_array
+        .get_services()
+        .into_iter()
+        .filter_map(|service| {
+            let expires_at = password_array.expires_at(&service).ok()??;
+            (expires_at <= soon).then_some((service, expires_at))
+        })
+        .collect();
+    due.sort_by_key(|(_, expires_at)| *expires_at);
+    due
+}
+
+/// Like `expired 3 days ago` or `expires in 5 days`
+pub fn describe(expires_at: u64) -> String {
+    let now = unix_time();
+    if expires_at <= now {
+        match (now - expires_at) / SECONDS_PER_DAY {
+            0 => String::from("expired today"),
+            1 => String::from("expired 1 day ago"),
+            days => format!("expired {days} days ago"),
+        }
+    } else {
+        match (expires_at - now).div_ceil(SECONDS_PER_DAY) {
+            1 => String::from("expires in 1 day"),
+            days => format!("expires in {days} days"),
+        }
+    }
+}
+
+/// Parses `YYYY-MM-DD` to unix time (midnight UTC)
+pub fn parse_date(value: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid date {value}, it should look like 2025-12-31");
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    let (year, month, day): (i64, u32, u32) = (
+        year.parse().map_err(|_| invalid())?,
+        month.parse().map_err(|_| invalid())?,
+        day.parse().map_err(|_| invalid())?,
+    );
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) || year < 1970 {
+        return Err(invalid());
+    }
+    Ok(days_from_civil(year, month, day) as u64 * SECONDS_PER_DAY)
+}
+
+/// Formats unix time as `YYYY-MM-DD`
+pub fn format_date(time: u64) -> String {
+    let (year, month, day) = civil_from_days((time / SECONDS_PER_DAY) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 (from <https://howardhinnant.github.io/date_algorithms.html>)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_expiry() {
+        assert_eq!(Expiry::parse("90d"), Ok(Expiry::Every(90)));
+        assert_eq!(Expiry::parse(" 2001-09-09 "), Ok(Expiry::Date(999993600)));
+        assert!(Expiry::parse("0d").is_err());
+        assert!(Expiry::parse("xd").is_err());
+        assert!(Expiry::parse("tomorrow").is_err());
+    }
+
+    #[test]
+    fn expires_at() {
+        assert_eq!(Expiry::Every(2).expires_at(100), 100 + 2 * SECONDS_PER_DAY);
+        assert_eq!(Expiry::Date(5).expires_at(100), 5);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_date("2000-03-01"), Ok(951868800));
+        assert_eq!(parse_date("2024-02-29"), Ok(1709164800));
+        for invalid in [
+            "2023-02-29",
+            "1900-02-29",
+            "2025-13-01",
+            "2025-04-31",
+            "2025-01-00",
+            "1969-12-31",
+            "2025-01",
+            "2025/01/01",
+        ] {
+            assert!(parse_date(invalid).is_err(), "{invalid}");
+        }
+        assert_eq!(format_date(999999999), "2001-09-09");
+        for date in ["1970-01-01", "2000-02-29", "2024-12-31", "2100-03-01"] {
+            assert_eq!(format_date(parse_date(date).unwrap()), date);
+        }
+        assert_eq!(Expiry::Date(0).to_string(), "1970-01-01");
+        assert_eq!(Expiry::Every(30).to_string(), "30d");
+    }
+
+    #[test]
+    fn describe_relative_to_now() {
+        let now = unix_time();
+        assert_eq!(describe(now), "expired today");
+        assert_eq!(describe(now - SECONDS_PER_DAY), "expired 1 day ago");
+        assert_eq!(describe(now - 3 * SECONDS_PER_DAY), "expired 3 days ago");
+        assert_eq!(describe(now + 10), "expires in 1 day");
+        assert_eq!(describe(now + 5 * SECONDS_PER_DAY), "expires in 5 days");
+    }
+}
@@ -1,20 +1,248 @@
 use crate::{
     ansi::clear_line,
-    cryptography::{decrypt, encrypt, generate_salt, hash},
-    ui::{NO_COMMANDS, NO_FLAGS, ProgressBar, YESES, input},
+    cryptography::{Cipher, Kdf, KdfParams, decrypt, encrypt, generate_salt, hash},
+    history::{History, Mutation},
+    journal::Journal,
+    otp::{Otp, OtpKind},
+    passwordrules::PasswordRules,
+    rotation::Expiry,
+    ui::{MASK, NO_COMMANDS, NO_FLAGS, ProgressBar, YESES, input, quit},
 };
 use argon2::password_hash::SaltString;
 use comfy_table::{ContentArrangement, Table};
 use rand_core::OsRng;
 use secrecy::{ExposeSecret, SecretString};
+use serde_json::{Value, json};
 use std::{
-    fs,
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
     io::{Write, stdout},
     iter::zip,
-    process::exit,
-    time::Duration,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+/// The format the vaults of this version are saved in, version 1 vaults don't have a `vault` file
+/// and always use the default [KdfParams]
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The format and cipher settings of a vault (stored in plain text in its `vault` file)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VaultSettings {
+    pub version: u32,
+    pub kdf: KdfParams,
+    pub cipher: Cipher,
+}
+
+impl Default for VaultSettings {
+    fn default() -> Self {
+        VaultSettings {
+            version: FORMAT_VERSION,
+            kdf: KdfParams::default(),
+            cipher: Cipher::Fernet,
+        }
+    }
+}
+
+impl VaultSettings {
+    /// Reads the settings of the vault in `directory`, a vault without a `vault` file is version 1
+    pub fn read(directory: &str) -> Result<VaultSettings, String> {
+        let Some(contents) = read_optional(&format!("{directory}/vault"))? else {
+            return Ok(VaultSettings {
+                version: 1,
+                ..VaultSettings::default()
+            });
+        };
+        let values = Metadata::parse(&contents);
+        let number = |key: &str| -> Result<u32, String> {
+            let value = values
+                .get(key)
+                .ok_or(format!("The vault file doesn't have {key}"))?;
+            value
+                .parse()
+                .map_err(|err| format!("Invalid {key} in the vault file: {err}"))
+        };
+        let settings = VaultSettings {
+            version: number("version")?,
+            kdf: KdfParams {
+                kdf: Kdf::parse(values.get("kdf").unwrap_or_default())?,
+                memory_cost: number("memory_cost")?,
+                time_cost: number("time_cost")?,
+                parallelism: number("parallelism")?,
+            },
+            cipher: Cipher::parse(values.get("cipher").unwrap_or_default())?,
+        };
+        if settings.version > FORMAT_VERSION {
+            return Err(format!(
+                "The vault is format version {}, this nspm only knows up to version {FORMAT_VERSION}",
+                settings.version
+            ));
+        }
+        settings.kdf.validate()?;
+        Ok(settings)
+    }
+    /// Writes the `vault` file (version 1 vaults don't have one)
+    fn write(&self, directory: &str) -> Result<(), String> {
+        if self.version < 2 {
+            return Ok(());
+        }
+        let mut values = Metadata::default();
+        values.set("version", Some(self.version.to_string()));
+        values.set("kdf", Some(self.kdf.kdf.to_string()));
+        values.set("memory_cost", Some(self.kdf.memory_cost.to_string()));
+        values.set("time_cost", Some(self.kdf.time_cost.to_string()));
+        values.set("parallelism", Some(self.kdf.parallelism.to_string()));
+        values.set("cipher", Some(self.cipher.to_string()));
+        fs::write(format!("{directory}/vault"), values.serialize())
+            .map_err(|err| format!("Error when writing the vault file: {err}"))
+    }
+}
+
+/// What can be known about a vault without the master password
+#[derive(Debug, Clone)]
+pub struct VaultInfo {
+    pub settings: VaultSettings,
+    pub entries: usize,
+    /// Entries with a 2FA secret
+    pub otps: usize,
+    /// Unix time of the newest file in the vault
+    pub last_modified: Option<u64>,
+}
+
+impl VaultInfo {
+    pub fn read(directory: &str) -> Result<VaultInfo, String> {
+        if !verify_directory(directory) {
+            return Err(String::from(
+                "directory either doesn't exist or doesn't have the correct files and directories",
+            ));
+        }
+        let count = |name: &str| {
+            fs::read_dir(format!("{directory}/{name}")).map_or(0, |entries| entries.count())
+        };
+        let mut last_modified = None;
+        for path in [
+            "master_password",
+            "vault",
+            "passwords",
+            "salts",
+            "services",
+            "otps",
+            "metadata",
+            "pending",
+        ] {
+            let path = format!("{directory}/{path}");
+            let mut files = vec![path.clone()];
+            if let Ok(entries) = fs::read_dir(&path) {
+                files.extend(
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path().to_string_lossy().to_string()),
+                );
+            }
+            for file in files {
+                let modified = fs::metadata(&file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|modified| modified.as_secs());
+                last_modified = last_modified.max(modified);
+            }
+        }
+        Ok(VaultInfo {
+            settings: VaultSettings::read(directory)?,
+            entries: count("passwords"),
+            otps: count("otps"),
+            last_modified,
+        })
+    }
+    pub fn to_json(&self) -> String {
+        let info = json!({
+            "version": self.settings.version,
+            "latest_version": FORMAT_VERSION,
+            "kdf": {
+                "algorithm": self.settings.kdf.kdf.to_string(),
+                "memory_cost": self.settings.kdf.memory_cost,
+                "time_cost": self.settings.kdf.time_cost,
+                "parallelism": self.settings.kdf.parallelism,
+            },
+            "cipher": self.settings.cipher.to_string(),
+            "entries": self.entries,
+            "otps": self.otps,
+            "last_modified": self.last_modified,
+        });
+        serde_json::to_string_pretty(&info).unwrap_or_default()
+    }
+}
+
+/// Plain text details of a [Password] that aren't secret (stored as key=value lines)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata(BTreeMap<String, String>);
+
+impl Metadata {
+    fn parse(contents: &str) -> Metadata {
+        Metadata(
+            contents
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.to_string(), unescape(value)))
+                .collect(),
+        )
+    }
+    fn serialize(&self) -> String {
+        self.0
+            .iter()
+            .map(|(key, value)| format!("{key}={}\n", escape(value)))
+            .collect()
+    }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|value| value.as_str())
+    }
+    /// Sets `key` to `value` or removes it when `value` is None
+    pub fn set(&mut self, key: &str, value: Option<String>) {
+        match value {
+            Some(value) => self.0.insert(key.to_string(), value),
+            None => self.0.remove(key),
+        };
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    fn changed_now() -> Metadata {
+        let mut metadata = Metadata::default();
+        metadata.set("changed", Some(unix_time().to_string()));
+        metadata
+    }
+}
+
+/// Seconds since the unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut characters = value.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            result.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
 /// A password with service and salt metadata
 #[derive(Debug, Clone)]
 pub struct Password {
@@ -22,63 +250,104 @@ pub struct Password {
     password: SecretString,
     salt: SaltString,
     key: SecretString,
+    kdf: KdfParams,
+    otp: Option<SecretString>,
+    metadata: Metadata,
+    /// A new password that replaces the current one once the service accepted it
+    pending: Option<SecretString>,
     is_encrypted: bool,
 }
 
 impl Password {
     /// creates a new password with a randomly generated salt
-    pub fn new(service: String, password: SecretString, key: SecretString) -> Password {
+    pub fn new(
+        service: String,
+        password: SecretString,
+        key: SecretString,
+        kdf: KdfParams,
+    ) -> Password {
         Password {
             service,
             password,
             salt: generate_salt(&mut OsRng).unwrap(),
             key,
+            kdf,
+            otp: None,
+            metadata: Metadata::changed_now(),
+            pending: None,
             is_encrypted: false,
         }
     }
-    /// saves [Password]'s details to 3 different files
-    pub fn save(
-        &self,
-        password_location: &str,
-        salt_location: &str,
-        service_location: &str,
-    ) -> Result<(), String> {
+    /// saves [Password]'s details to 3 different files in `directory` (more if it has an OTP
+    /// secret, metadata or a pending password), `index` is the number at the end of the files
+    pub fn save(&self, directory: &str, index: usize) -> Result<(), String> {
         if !self.is_encrypted {
             panic!("not encrypted");
         }
+        let password_location = format!("{directory}/passwords/password_{index}");
+        let salt_location = format!("{directory}/salts/salt_{index}");
+        let service_location = format!("{directory}/services/service_{index}");
+        let otp_location = format!("{directory}/otps/otp_{index}");
+        let metadata_location = format!("{directory}/metadata/metadata_{index}");
+        let pending_location = format!("{directory}/pending/pending_{index}");
         fs::write(password_location, self.password.expose_secret())
             .map_err(|err| format!("Error when writing password: {err}"))?;
         fs::write(salt_location, self.salt.as_str())
             .map_err(|err| format!("Error when writing salt: {err}"))?;
         fs::write(service_location, &self.service)
             .map_err(|err| format!("Error when writing service: {err}"))?;
+        if let Some(otp) = &self.otp {
+            fs::write(otp_location, otp.expose_secret())
+                .map_err(|err| format!("Error when writing OTP secret: {err}"))?;
+        }
+        if !self.metadata.is_empty() {
+            fs::write(metadata_location, self.metadata.serialize())
+                .map_err(|err| format!("Error when writing metadata: {err}"))?;
+        }
+        if let Some(pending) = &self.pending {
+            fs::write(pending_location, pending.expose_secret())
+                .map_err(|err| format!("Error when writing pending password: {err}"))?;
+        }
         Ok(())
     }
-    /// Makes encrypted [Password] from the 3 files that [Password::save] writes
-    /// Assumes that the password is encrypted
+    /// Makes encrypted [Password] from the files that [Password::save] writes
+    /// Assumes that the password is encrypted, the OTP, metadata and pending files are optional
     ///
     /// # Panics
     /// Panics if either one of the file locations doesn't exist or if the salt stored at salt
     /// location is not in base64
     pub fn load(
-        password_location: &str,
-        salt_location: &str,
-        service_location: &str,
+        directory: &str,
+        index: usize,
         master_password: &str,
+        kdf: KdfParams,
     ) -> Result<Password, String> {
+        let password_location = format!("{directory}/passwords/password_{index}");
+        let salt_location = format!("{directory}/salts/salt_{index}");
+        let service_location = format!("{directory}/services/service_{index}");
+        let otp = read_optional(&format!("{directory}/otps/otp_{index}"))?.map(SecretString::from);
+        let metadata = read_optional(&format!("{directory}/metadata/metadata_{index}"))?
+            .map(|contents| Metadata::parse(&contents))
+            .unwrap_or_default();
+        let pending =
+            read_optional(&format!("{directory}/pending/pending_{index}"))?.map(SecretString::from);
         Ok(Password {
             password: SecretString::from(
-                fs::read_to_string(password_location)
+                fs::read_to_string(&password_location)
                     .map_err(|err| format!("Failed to read: {password_location}, Error: {err}"))?,
             ),
             salt: SaltString::from_b64(
-                &fs::read_to_string(salt_location)
+                &fs::read_to_string(&salt_location)
                     .map_err(|err| format!("Failed to read {salt_location}, Error: {err}"))?,
             )
             .map_err(|err| format!("Failed to decode from base64: {err}"))?,
-            service: fs::read_to_string(service_location)
+            service: fs::read_to_string(&service_location)
                 .map_err(|err| format!("Failed to read {service_location}: {err}"))?,
             key: SecretString::from(master_password),
+            kdf,
+            otp,
+            metadata,
+            pending,
             is_encrypted: true,
         })
     }
@@ -92,7 +361,24 @@ impl Password {
             self.password.expose_secret().as_bytes(),
             self.key.expose_secret().as_bytes(),
             &self.salt,
+            &self.kdf,
         ));
+        if let Some(otp) = &self.otp {
+            self.otp = Some(SecretString::from(encrypt(
+                otp.expose_secret().as_bytes(),
+                self.key.expose_secret().as_bytes(),
+                &self.salt,
+                &self.kdf,
+            )));
+        }
+        if let Some(pending) = &self.pending {
+            self.pending = Some(SecretString::from(encrypt(
+                pending.expose_secret().as_bytes(),
+                self.key.expose_secret().as_bytes(),
+                &self.salt,
+                &self.kdf,
+            )));
+        }
         self.key = SecretString::from("");
         self.is_encrypted = true;
         Ok(())
@@ -110,7 +396,24 @@ impl Password {
             self.password.expose_secret().as_bytes(),
             self.key.expose_secret().as_bytes(),
             &self.salt,
+            &self.kdf,
         )?;
+        if let Some(otp) = &self.otp {
+            self.otp = Some(decrypt(
+                otp.expose_secret().as_bytes(),
+                self.key.expose_secret().as_bytes(),
+                &self.salt,
+                &self.kdf,
+            )?);
+        }
+        if let Some(pending) = &self.pending {
+            self.pending = Some(decrypt(
+                pending.expose_secret().as_bytes(),
+                self.key.expose_secret().as_bytes(),
+                &self.salt,
+                &self.kdf,
+            )?);
+        }
         self.is_encrypted = false;
         Ok(())
     }
@@ -119,6 +422,184 @@ impl Password {
             return Err("is encrypted");
         }
         self.password = new_pass;
+        self.metadata.set("changed", Some(unix_time().to_string()));
+        // a fixed expiry date is done once the password changed
+        self.metadata.set("expires", None);
+        Ok(())
+    }
+    /// The entry as a journal record (with its secrets, the record is encrypted)
+    fn to_record(&self) -> Value {
+        json!({
+            "service": self.service,
+            "password": self.password.expose_secret(),
+            "salt": self.salt.as_str(),
+            "otp": self.otp.as_ref().map(|otp| otp.expose_secret()),
+            "pending": self.pending.as_ref().map(|pending| pending.expose_secret()),
+            "metadata": self.metadata.0,
+        })
+    }
+    /// Reads an entry written with [to_record][Password::to_record]
+    fn from_record(record: &Value, key: SecretString, kdf: KdfParams) -> Result<Password, String> {
+        let broken = || String::from("The journal has a broken entry");
+        let string = |field: &str| record[field].as_str().map(SecretString::from);
+        let metadata = record["metadata"]
+            .as_object()
+            .ok_or_else(broken)?
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect();
+        Ok(Password {
+            service: record["service"].as_str().ok_or_else(broken)?.to_string(),
+            password: string("password").ok_or_else(broken)?,
+            salt: SaltString::from_b64(record["salt"].as_str().ok_or_else(broken)?)
+                .map_err(|_| broken())?,
+            key,
+            kdf,
+            otp: string("otp"),
+            metadata: Metadata(metadata),
+            pending: string("pending"),
+            is_encrypted: false,
+        })
+    }
+    /// The fields that are different in `other` (the same entry later on), "changed" is left
+    /// out since it only changes together with the password
+    fn changed_fields(&self, other: &Password) -> Vec<FieldChange> {
+        let mut fields = vec![];
+        let secrets = [
+            ("password", Some(&self.password), Some(&other.password)),
+            ("2FA secret", self.otp.as_ref(), other.otp.as_ref()),
+            (
+                "pending password",
+                self.pending.as_ref(),
+                other.pending.as_ref(),
+            ),
+        ];
+        let masked = |secret: Option<&SecretString>| secret.map_or("none", |_| MASK).to_string();
+        for (field, before, after) in secrets {
+            if before.map(|s| s.expose_secret()) != after.map(|s| s.expose_secret()) {
+                fields.push(FieldChange {
+                    field: field.to_string(),
+                    before: masked(before),
+                    after: masked(after),
+                });
+            }
+        }
+        let keys: BTreeSet<&String> = self
+            .metadata
+            .0
+            .keys()
+            .chain(other.metadata.0.keys())
+            .collect();
+        for key in keys.into_iter().filter(|key| *key != "changed") {
+            let before = self.metadata.get(key);
+            let after = other.metadata.get(key);
+            if before != after {
+                fields.push(FieldChange {
+                    field: key.replace('_', " "),
+                    before: before.unwrap_or("none").to_string(),
+                    after: after.unwrap_or("none").to_string(),
+                });
+            }
+        }
+        fields
+    }
+    fn set_otp(&mut self, otp: &Otp) -> Result<(), &str> {
+        if self.is_encrypted {
+            return Err("is encrypted");
+        }
+        self.otp = Some(SecretString::from(otp.to_uri()));
+        Ok(())
+    }
+    /// Moves the HOTP counter forward to the one of `otp` when the entry has the same secret
+    fn advance_hotp(&mut self, otp: &Otp) {
+        let OtpKind::Hotp { counter } = otp.kind else {
+            return;
+        };
+        let Ok(Some(mut current)) = self.get_otp() else {
+            return;
+        };
+        if let OtpKind::Hotp {
+            counter: current_counter,
+        } = &mut current.kind
+            && current.secret == otp.secret
+            && *current_counter < counter
+        {
+            *current_counter = counter;
+            let _ = self.set_otp(&current);
+        }
+    }
+    /// Writes only the OTP file of an entry that [save][Password::save] wrote at `index`
+    fn save_otp(&self, directory: &str, index: usize) -> Result<(), String> {
+        let mut encrypted = self.clone();
+        encrypted.encrypt()?;
+        let Some(otp) = &encrypted.otp else {
+            return Ok(());
+        };
+        let otp_location = format!("{directory}/otps/otp_{index}");
+        let temporary_location = format!("{otp_location}_tmp");
+        fs::write(&temporary_location, otp.expose_secret())
+            .and_then(|_| fs::rename(&temporary_location, &otp_location))
+            .map_err(|err| format!("Error when writing OTP secret: {err}"))
+    }
+    fn get_otp(&self) -> Result<Option<Otp>, String> {
+        if self.is_encrypted {
+            return Err("is encrypted".to_string());
+        }
+        self.otp
+            .as_ref()
+            .map(|otp| Otp::from_uri(otp.expose_secret()))
+            .transpose()
+    }
+}
+
+/// A field of an entry that changed, secrets are masked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} → {}", self.field, self.before, self.after)
+    }
+}
+
+/// An entry that changed since the vault was loaded or saved (see [PasswordArray::changes])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(String),
+    Edited {
+        service: String,
+        fields: Vec<FieldChange>,
+    },
+    Renamed {
+        from: String,
+        to: String,
+        fields: Vec<FieldChange>,
+    },
+    Removed(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = match self {
+            Change::Added(service) => return write!(f, "+ {service}"),
+            Change::Removed(service) => return write!(f, "- {service}"),
+            Change::Edited { service, fields } => {
+                write!(f, "~ {service}")?;
+                fields
+            }
+            Change::Renamed { from, to, fields } => {
+                write!(f, "~ {from} → {to}")?;
+                fields
+            }
+        };
+        if !fields.is_empty() {
+            let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+            write!(f, " ({})", fields.join(", "))?;
+        }
         Ok(())
     }
 }
@@ -126,8 +607,15 @@ impl Password {
 /// An array of [`Password`] that's better than an array of [`Password`]
 pub struct PasswordArray {
     passwords: Vec<Password>,
+    /// The entries as they were loaded or last saved, to find out what changed since
+    saved: Vec<Password>,
+    history: History,
+    /// Where the changes are written to until they're saved (see
+    /// [enable_journal][PasswordArray::enable_journal])
+    journal: Option<Journal>,
     master_password: SecretString,
     directory_name: String,
+    settings: VaultSettings,
 }
 
 impl PasswordArray {
@@ -135,8 +623,12 @@ impl PasswordArray {
     pub fn new(master_password: SecretString, directory_name: String) -> PasswordArray {
         PasswordArray {
             passwords: vec![],
+            saved: vec![],
+            history: History::default(),
+            journal: None,
             master_password,
             directory_name,
+            settings: VaultSettings::default(),
         }
     }
     /// Saves all passwords in a directory that can be loaded with [load][PasswordArray::load]
@@ -151,9 +643,10 @@ impl PasswordArray {
                 "y".to_string(),
                 NO_COMMANDS,
                 NO_FLAGS,
-            );
+            )
+            .map_err(|_| format!("{temporary_directory} is left over, remove it to save"))?;
             if !YESES.contains(&&yn.to_lowercase()[..]) {
-                exit(0)
+                quit(0)
             }
             fs::remove_dir_all(&temporary_directory).map_err(|err| {
                 format!("Error when removing existing temporary directory: {err}")
@@ -164,24 +657,25 @@ impl PasswordArray {
             clear_line();
             print_wrapper(format!("{progress_bar} Making temporary directory"));
         }
-        initialize_directory(&temporary_directory, self.master_password.expose_secret())?;
+        initialize_directory(
+            &temporary_directory,
+            self.master_password.expose_secret(),
+            &self.settings,
+        )?;
         if print_progress_bar {
             progress_bar.increase_n();
             clear_line();
             print_wrapper(format!("{progress_bar} Made temporary directory"));
         }
-        self.encrypt(print_progress_bar, &mut progress_bar);
-        for (index, password) in self.passwords.iter().enumerate() {
-            let password_location = format!("{temporary_directory}/passwords/password_{index}");
-            let service_location = format!("{temporary_directory}/services/service_{index}");
-            let salt_location = format!("{temporary_directory}/salts/salt_{index}");
+        let encrypted = self.encrypted(print_progress_bar, &mut progress_bar);
+        for (index, password) in encrypted.iter().enumerate() {
             if print_progress_bar {
                 progress_bar.increase_n();
                 clear_line();
                 print_wrapper(format!("{progress_bar} Saving, {}", password.service));
                 sleep(45);
             }
-            password.save(&password_location, &salt_location, &service_location)?;
+            password.save(&temporary_directory, index)?;
         }
         if print_progress_bar {
             progress_bar.increase_n();
@@ -196,8 +690,15 @@ impl PasswordArray {
             progress_bar.increase_n();
             clear_line();
             print_wrapper(format!("{progress_bar} Moved temporary directory"));
+            println!();
+        }
+        self.saved = self.passwords.clone();
+        self.history.mark_saved();
+        if let Some(journal) = &mut self.journal
+            && let Err(error) = journal.remove()
+        {
+            eprintln!("{error}");
         }
-        println!();
         Ok(())
     }
     /// Loads a directory to a [PasswordArray]
@@ -209,16 +710,17 @@ impl PasswordArray {
                 "directory either doesn't exist or doesn't have the correct files and directories",
             ));
         }
+        self.settings = VaultSettings::read(&self.directory_name)?;
         let amount_of_passwords: usize = fs::read_dir(format!("{}/passwords", self.directory_name))
             .unwrap()
             .count();
         let mut progress_bar = ProgressBar::new(amount_of_passwords as u32 * 3);
         for index in 0..amount_of_passwords {
             self.passwords.push(Password::load(
-                format!("{}/passwords/password_{index}", self.directory_name).as_str(),
-                format!("{}/salts/salt_{index}", self.directory_name).as_str(),
-                format!("{}/services/service_{index}", self.directory_name).as_str(),
+                &self.directory_name,
+                index,
                 self.master_password.expose_secret(),
+                self.settings.kdf,
             )?);
             if print_progress_bar {
                 progress_bar.increase_n();
@@ -231,25 +733,53 @@ impl PasswordArray {
             }
         }
         self.decrypt(print_progress_bar, &mut progress_bar)?;
-        println!();
+        if print_progress_bar {
+            println!();
+        }
+        self.saved = self.passwords.clone();
         Ok(())
     }
+    /// Forgets the master password (and the copies the entries keep as their key, with the ones
+    /// kept for undoing), the vault can't be saved until [unlock][PasswordArray::unlock]
+    pub fn lock(&mut self) {
+        self.master_password = SecretString::from("");
+        for password in self.entries_mut() {
+            password.key = SecretString::from("");
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.forget_key();
+        }
+    }
+    /// Gives the entries their key back after [lock][PasswordArray::lock], the master password
+    /// has to be checked first
+    pub fn unlock(&mut self, master_password: SecretString) {
+        for password in self.entries_mut() {
+            password.key = master_password.clone();
+        }
+        self.master_password = master_password;
+    }
+    /// The entries, the ones from when it was saved and the ones kept for undoing
+    fn entries_mut(&mut self) -> impl Iterator<Item = &mut Password> {
+        self.passwords
+            .iter_mut()
+            .chain(self.saved.iter_mut())
+            .chain(self.history.passwords_mut())
+    }
     /// Adds a password to [PasswordArray]
     pub fn add_password(&mut self, service: String, password: SecretString) -> Result<(), &str> {
-        if self
-            .passwords
-            .iter()
-            .map(|p| &p.service)
-            .collect::<Vec<_>>()
-            .contains(&&service)
-        {
+        if self.index_of(&service).is_some() {
             return Err("service name is taken");
         }
-        self.passwords.push(Password::new(
-            service,
+        let password = Password::new(
+            service.clone(),
             password,
             self.master_password.clone(),
-        ));
+            self.settings.kdf,
+        );
+        self.execute(
+            format!("Add {service}"),
+            Mutation::Insert(self.passwords.len(), password),
+        );
         Ok(())
     }
     /// (hopefully self explanatory)
@@ -258,31 +788,275 @@ impl PasswordArray {
         service_name: String,
         new_pass: SecretString,
     ) -> Result<(), &str> {
-        let index = &self
-            .passwords
-            .iter()
-            .map(|p| &p.service)
-            .position(|s| *s == service_name);
-        if index.is_none() {
-            return Err("couldn't find service");
-        }
-        let index = index.unwrap();
-        let a: &mut Password = self.passwords.get_mut(index).unwrap();
-        let _ = a.edit_password(new_pass);
+        let index = self
+            .index_of(&service_name)
+            .ok_or("couldn't find service")?;
+        let mut password = self.passwords[index].clone();
+        let _ = password.edit_password(new_pass);
+        self.replace(format!("Edit {service_name}"), index, password);
         Ok(())
     }
     /// (guess)
     pub fn remove_password(&mut self, service_name: String) -> Result<(), &str> {
-        let index = &self
-            .passwords
+        let index = self
+            .index_of(&service_name)
+            .ok_or("couldn't find service")?;
+        let password = self.passwords[index].clone();
+        self.execute(
+            format!("Remove {service_name}"),
+            Mutation::Remove(index, password),
+        );
+        Ok(())
+    }
+    /// Changes the name of a service, everything else stays the same
+    pub fn rename_service(&mut self, service_name: &str, new_name: String) -> Result<(), &str> {
+        let index = self.index_of(service_name).ok_or("couldn't find service")?;
+        if self.index_of(&new_name).is_some() {
+            return Err("service name is taken");
+        }
+        let mut password = self.passwords[index].clone();
+        password.service = new_name;
+        self.replace(
+            format!("Rename {service_name} to {}", password.service),
+            index,
+            password,
+        );
+        Ok(())
+    }
+    /// Undoes the last change to the entries, returns what it was (None if there's nothing to
+    /// undo)
+    pub fn undo(&mut self) -> Option<String> {
+        let (description, mutations) = self.history.undo(&mut self.passwords)?;
+        let description = description.to_string();
+        self.write_journal(&mutations);
+        self.refresh_keys();
+        Some(description)
+    }
+    /// Does the last undone change again, returns what it was (None if there's nothing to redo)
+    pub fn redo(&mut self) -> Option<String> {
+        let (description, mutations) = self.history.redo(&mut self.passwords)?;
+        let description = description.to_string();
+        self.write_journal(&mutations);
+        self.refresh_keys();
+        Some(description)
+    }
+    /// If the entries changed since the vault was loaded or saved (undoing or reverting back to
+    /// that point counts as unchanged)
+    pub fn has_unsaved_changes(&self) -> bool {
+        !self.history.is_saved() && !self.changes().is_empty()
+    }
+    /// Entries from the history could still have the key and KDF they had back then
+    fn refresh_keys(&mut self) {
+        for password in self.passwords.iter_mut() {
+            password.key = self.master_password.clone();
+            password.kdf = self.settings.kdf;
+        }
+    }
+    fn index_of(&self, service_name: &str) -> Option<usize> {
+        self.passwords
             .iter()
-            .map(|p| &p.service)
-            .position(|s| **s == service_name);
-        if index.is_none() {
-            return Err("couldn't find service");
+            .position(|p| p.service == service_name)
+    }
+    /// Does `mutation` as a change that can be undone (it's written to the journal first)
+    fn execute(&mut self, description: String, mutation: Mutation) {
+        self.write_journal(std::slice::from_ref(&mutation));
+        self.history
+            .execute(description, mutation, &mut self.passwords);
+    }
+    /// Writes every change to a [Journal] next to the vault from now on, so they can be replayed
+    /// with [replay_journal][PasswordArray::replay_journal] if they're never saved
+    pub fn enable_journal(&mut self) {
+        self.journal = Some(Journal::new(&self.directory_name));
+    }
+    /// If the journal has changes that weren't saved (by a session that didn't end)
+    pub fn has_journal(&self) -> bool {
+        self.journal.as_ref().is_some_and(Journal::exists)
+    }
+    /// Applies the changes in the journal to the loaded entries (as one change that can be
+    /// undone), they stay in the journal until they're saved
+    pub fn replay_journal(&mut self) -> Result<(), String> {
+        let Some(mut journal) = self.journal.take() else {
+            return Ok(());
+        };
+        let result = self.replay(&mut journal);
+        self.journal = Some(journal);
+        result
+    }
+    /// Deletes the journal, the changes in it are lost
+    pub fn discard_journal(&mut self) -> Result<(), String> {
+        match &mut self.journal {
+            Some(journal) => journal.remove(),
+            None => Ok(()),
+        }
+    }
+    fn replay(&mut self, journal: &mut Journal) -> Result<(), String> {
+        let records = journal.read(&self.master_password, &self.settings.kdf)?;
+        let Some((header, records)) = records.split_first() else {
+            return Ok(());
+        };
+        let salts: Vec<&str> = self.passwords.iter().map(|p| p.salt.as_str()).collect();
+        if header["entries"] != json!(salts) {
+            return Err(String::from(
+                "The journal is from another version of the vault (it was saved since)",
+            ));
+        }
+        // checked before anything is applied so a broken journal doesn't change half of the vault
+        let mut entries = self.passwords.clone();
+        let mut mutations = vec![];
+        for record in records {
+            let mutation = self.mutation_from_record(record, &entries)?;
+            mutation.apply(&mut entries);
+            mutations.push(mutation);
+        }
+        self.history
+            .begin(String::from("Replay the changes from the journal"));
+        for mutation in mutations {
+            self.execute(String::new(), mutation);
+        }
+        self.history.end();
+        Ok(())
+    }
+    /// Appends `mutations` to the journal (if it's enabled), the journal starts with the salts of
+    /// the entries it was made for
+    fn write_journal(&mut self, mutations: &[Mutation]) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        let mut records = vec![];
+        if !journal.exists() {
+            let salts: Vec<&str> = self.passwords.iter().map(|p| p.salt.as_str()).collect();
+            records.push(json!({ "entries": salts }));
+        }
+        records.extend(mutations.iter().map(|mutation| match mutation {
+            Mutation::Insert(index, password) => {
+                json!({ "op": "insert", "index": index, "entry": password.to_record() })
+            }
+            Mutation::Remove(index, password) => {
+                json!({ "op": "remove", "index": index, "service": password.service })
+            }
+            Mutation::Replace(index, _, password) => {
+                json!({ "op": "replace", "index": index, "entry": password.to_record() })
+            }
+        }));
+        for record in records {
+            if let Err(error) = journal.append(&record, &self.master_password, &self.settings.kdf) {
+                eprintln!("{error}");
+                return;
+            }
+        }
+    }
+    /// The [Mutation] of a journal record, `entries` are the entries it's applied to
+    fn mutation_from_record(
+        &self,
+        record: &Value,
+        entries: &[Password],
+    ) -> Result<Mutation, String> {
+        let mismatch = || String::from("The journal doesn't match the vault");
+        let index = record["index"].as_u64().ok_or_else(mismatch)? as usize;
+        let entry = || {
+            Password::from_record(
+                &record["entry"],
+                self.master_password.clone(),
+                self.settings.kdf,
+            )
+        };
+        match record["op"].as_str() {
+            Some("insert") if index <= entries.len() => Ok(Mutation::Insert(index, entry()?)),
+            Some("remove")
+                if entries
+                    .get(index)
+                    .is_some_and(|p| record["service"] == p.service.as_str()) =>
+            {
+                Ok(Mutation::Remove(index, entries[index].clone()))
+            }
+            Some("replace") if index < entries.len() => {
+                Ok(Mutation::Replace(index, entries[index].clone(), entry()?))
+            }
+            _ => Err(mismatch()),
+        }
+    }
+    /// Replaces the entry at `index` with `password` as a change that can be undone
+    fn replace(&mut self, description: String, index: usize, password: Password) {
+        let before = self.passwords[index].clone();
+        self.execute(description, Mutation::Replace(index, before, password));
+    }
+    /// What changed since the vault was loaded or saved, in the order of the entries (removed
+    /// entries last)
+    pub fn changes(&self) -> Vec<Change> {
+        let mut changes = vec![];
+        for password in self.passwords.iter() {
+            let Some(saved) = self.saved.iter().find(|saved| saved.salt == password.salt) else {
+                changes.push(Change::Added(password.service.clone()));
+                continue;
+            };
+            let fields = saved.changed_fields(password);
+            if saved.service != password.service {
+                changes.push(Change::Renamed {
+                    from: saved.service.clone(),
+                    to: password.service.clone(),
+                    fields,
+                });
+            } else if !fields.is_empty() {
+                changes.push(Change::Edited {
+                    service: password.service.clone(),
+                    fields,
+                });
+            }
+        }
+        for saved in self.saved.iter() {
+            if !self.passwords.iter().any(|p| p.salt == saved.salt) {
+                changes.push(Change::Removed(saved.service.clone()));
+            }
+        }
+        changes
+    }
+    /// Undoes one of the [changes][PasswordArray::changes], the entry goes back to how it was
+    /// loaded or saved
+    pub fn revert(&mut self, change: &Change) -> Result<(), String> {
+        let service = match change {
+            Change::Added(service) => {
+                let index = self
+                    .index_of(service)
+                    .ok_or(format!("couldn't find service {service}"))?;
+                let password = self.passwords[index].clone();
+                self.execute(
+                    format!("Revert {service}"),
+                    Mutation::Remove(index, password),
+                );
+                return Ok(());
+            }
+            Change::Removed(service) => service,
+            Change::Edited { service, .. } | Change::Renamed { to: service, .. } => service,
+        };
+        let current = self.index_of(service);
+        let saved = match (change, current) {
+            (Change::Removed(_), Some(_)) => {
+                return Err(format!("service name {service} is taken"));
+            }
+            (Change::Removed(_), None) => self.saved.iter().find(|saved| {
+                saved.service == *service && !self.passwords.iter().any(|p| p.salt == saved.salt)
+            }),
+            (_, Some(index)) => {
+                let salt = &self.passwords[index].salt;
+                self.saved.iter().find(|saved| saved.salt == *salt)
+            }
+            (_, None) => None,
+        };
+        let mut saved = saved
+            .cloned()
+            .ok_or(format!("couldn't find the changes of {service}"))?;
+        if let Change::Renamed { from, .. } = change
+            && self.passwords.iter().any(|p| p.service == *from)
+        {
+            return Err(format!("service name {from} is taken"));
+        }
+        saved.key = self.master_password.clone();
+        saved.kdf = self.settings.kdf;
+        let description = format!("Revert {service}");
+        match current {
+            Some(index) => self.replace(description, index, saved),
+            None => self.execute(description, Mutation::Insert(self.passwords.len(), saved)),
         }
-        let index = index.unwrap();
-        self.passwords.remove(index);
         Ok(())
     }
     fn decrypt(
@@ -305,8 +1079,11 @@ impl PasswordArray {
         }
         Ok(())
     }
-    fn encrypt(&mut self, print_progress_bar: bool, progress_bar: &mut ProgressBar) {
-        for password in self.passwords.iter_mut() {
+    /// Encrypted copies of the passwords, the [PasswordArray] itself stays decrypted so it can
+    /// still be used after saving
+    fn encrypted(&self, print_progress_bar: bool, progress_bar: &mut ProgressBar) -> Vec<Password> {
+        let mut passwords = self.passwords.clone();
+        for password in passwords.iter_mut() {
             if print_progress_bar {
                 progress_bar.increase_n();
                 clear_line();
@@ -319,11 +1096,17 @@ impl PasswordArray {
                 print_wrapper(format!("{progress_bar} Encrypted, {}", password.service));
             }
         }
+        passwords
     }
-    pub fn table(&mut self) -> Table {
+    /// The services and their passwords, the passwords are masked unless `reveal`
+    pub fn table(&self, reveal: bool) -> Table {
         let mut passwords = vec![];
         for password in self.passwords.iter() {
-            passwords.push(password.password.expose_secret());
+            if reveal {
+                passwords.push(password.password.expose_secret());
+            } else {
+                passwords.push(MASK);
+            }
         }
         let mut result = vec![];
         for (service, password) in zip(
@@ -343,9 +1126,280 @@ impl PasswordArray {
             .add_rows(result);
         tables
     }
+    /// The directory of the vault
+    pub fn get_directory(&self) -> &str {
+        &self.directory_name
+    }
     pub fn get_services(&self) -> Vec<String> {
         self.passwords.iter().map(|p| p.service.clone()).collect()
     }
+    /// The (decrypted) password of a service
+    pub fn get_password(&self, service_name: &str) -> Result<&SecretString, String> {
+        self.passwords
+            .iter()
+            .find(|p| p.service == service_name)
+            .map(|p| &p.password)
+            .ok_or(format!("couldn't find service {service_name}"))
+    }
+    /// Adds (or replaces) the OTP secret of a service
+    pub fn set_otp(&mut self, service_name: String, otp: &Otp) -> Result<(), &str> {
+        let index = self
+            .index_of(&service_name)
+            .ok_or("couldn't find service")?;
+        let mut password = self.passwords[index].clone();
+        if password.set_otp(otp).is_err() {
+            return Err("is encrypted");
+        }
+        self.replace(
+            format!("Set the 2FA secret of {service_name}"),
+            index,
+            password,
+        );
+        Ok(())
+    }
+    /// Gets the OTP of a service
+    pub fn get_otp(&self, service_name: &str) -> Result<Otp, String> {
+        self.passwords
+            .iter()
+            .find(|p| p.service == service_name)
+            .ok_or("couldn't find service")?
+            .get_otp()?
+            .ok_or(format!("{service_name} doesn't have an OTP secret"))
+    }
+    /// Adds every [Otp] as a new service (with an empty password), skipping the ones that are
+    /// already in the [PasswordArray]. Returns the added services and the skipped ones with a reason
+    pub fn import_otps(&mut self, otps: Vec<Otp>) -> (Vec<String>, Vec<(String, String)>) {
+        let (mut imported, mut duplicates) = (vec![], vec![]);
+        self.history.begin(String::from("Import 2FA seeds"));
+        for otp in otps {
+            let service = otp.service_name();
+            let existing = self
+                .passwords
+                .iter()
+                .find(|p| matches!(p.get_otp(), Ok(Some(o)) if o.secret == otp.secret));
+            if let Some(existing) = existing {
+                duplicates.push((service, format!("same secret as {}", existing.service)));
+                continue;
+            }
+            if self
+                .add_password(service.clone(), SecretString::from(""))
+                .is_err()
+            {
+                duplicates.push((service, String::from("service name is taken")));
+                continue;
+            }
+            let _ = self.set_otp(service.clone(), &otp);
+            imported.push(service);
+        }
+        self.history.end();
+        (imported, duplicates)
+    }
+    /// Stores the passwordrules of a service (None removes them)
+    pub fn set_password_rules(
+        &mut self,
+        service_name: &str,
+        rules: Option<&PasswordRules>,
+    ) -> Result<(), &str> {
+        let index = self.index_of(service_name).ok_or("couldn't find service")?;
+        let mut password = self.passwords[index].clone();
+        password
+            .metadata
+            .set("passwordrules", rules.map(|r| r.to_string()));
+        self.replace(
+            format!("Set the password rules of {service_name}"),
+            index,
+            password,
+        );
+        Ok(())
+    }
+    /// Gets the passwordrules of a service if it has any
+    pub fn get_password_rules(&self, service_name: &str) -> Result<Option<PasswordRules>, String> {
+        self.passwords
+            .iter()
+            .find(|p| p.service == service_name)
+            .ok_or("couldn't find service")?
+            .metadata
+            .get("passwordrules")
+            .map(PasswordRules::parse)
+            .transpose()
+    }
+    /// When the password of a service was last changed (unix time), None for entries made before
+    /// this was tracked
+    pub fn get_changed(&self, service_name: &str) -> Result<Option<u64>, String> {
+        Ok(self
+            .passwords
+            .iter()
+            .find(|p| p.service == service_name)
+            .ok_or("couldn't find service")?
+            .metadata
+            .get("changed")
+            .and_then(|changed| changed.parse().ok()))
+    }
+    /// Sets (or removes) the expiry date or rotation interval of a service, a rotation interval
+    /// starts now if it isn't known when the password was last changed
+    pub fn set_expiry(&mut self, service_name: &str, expiry: Option<Expiry>) -> Result<(), &str> {
+        let index = self.index_of(service_name).ok_or("couldn't find service")?;
+        let mut password = self.passwords[index].clone();
+        let metadata = &mut password.metadata;
+        metadata.set("expires", None);
+        metadata.set("rotate_days", None);
+        match expiry {
+            Some(Expiry::Date(date)) => metadata.set("expires", Some(date.to_string())),
+            Some(Expiry::Every(days)) => {
+                metadata.set("rotate_days", Some(days.to_string()));
+                if metadata.get("changed").is_none() {
+                    metadata.set("changed", Some(unix_time().to_string()));
+                }
+            }
+            None => {}
+        }
+        self.replace(format!("Set the expiry of {service_name}"), index, password);
+        Ok(())
+    }
+    /// Gets the expiry date or rotation interval of a service if it has one
+    pub fn get_expiry(&self, service_name: &str) -> Result<Option<Expiry>, String> {
+        let metadata = &self
+            .passwords
+            .iter()
+            .find(|p| p.service == service_name)
+            .ok_or("couldn't find service")?
+            .metadata;
+        let number = |key: &str| metadata.get(key).and_then(|value| value.parse().ok());
+        Ok(number("expires")
+            .map(Expiry::Date)
+            .or(number("rotate_days").map(Expiry::Every)))
+    }
+    /// When the password of a service expires (unix time)
+    pub fn expires_at(&self, service_name: &str) -> Result<Option<u64>, String> {
+        let changed = self.get_changed(service_name)?.unwrap_or_else(unix_time);
+        Ok(self
+            .get_expiry(service_name)?
+            .map(|expiry| expiry.expires_at(changed)))
+    }
+    /// Stores a new password that replaces the current one with
+    /// [confirm_pending][PasswordArray::confirm_pending] (None throws it away)
+    pub fn set_pending(
+        &mut self,
+        service_name: &str,
+        pending: Option<SecretString>,
+    ) -> Result<(), &str> {
+        let index = self.index_of(service_name).ok_or("couldn't find service")?;
+        let description = match pending {
+            Some(_) => format!("Set the pending password of {service_name}"),
+            None => format!("Discard the pending password of {service_name}"),
+        };
+        let mut password = self.passwords[index].clone();
+        password.pending = pending;
+        self.replace(description, index, password);
+        Ok(())
+    }
+    /// The new password of a service that hasn't been confirmed yet
+    pub fn get_pending(&self, service_name: &str) -> Result<Option<&SecretString>, String> {
+        Ok(self
+            .passwords
+            .iter()
+            .find(|p| p.service == service_name)
+            .ok_or("couldn't find service")?
+            .pending
+            .as_ref())
+    }
+    /// Replaces the password of a service with its pending one
+    pub fn confirm_pending(&mut self, service_name: &str) -> Result<(), String> {
+        let index = self.index_of(service_name).ok_or("couldn't find service")?;
+        let mut password = self.passwords[index].clone();
+        let pending = password
+            .pending
+            .take()
+            .ok_or(format!("{service_name} doesn't have a pending password"))?;
+        password.edit_password(pending)?;
+        self.replace(
+            format!("Confirm the pending password of {service_name}"),
+            index,
+            password,
+        );
+        Ok(())
+    }
+    /// Sets the tags of a service (like `work` or `incident-42`)
+    pub fn set_tags(&mut self, service_name: &str, tags: &[String]) -> Result<(), &str> {
+        let index = self.index_of(service_name).ok_or("couldn't find service")?;
+        let mut password = self.passwords[index].clone();
+        let tags: Vec<&str> = tags
+            .iter()
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        password
+            .metadata
+            .set("tags", (!tags.is_empty()).then(|| tags.join(",")));
+        self.replace(format!("Edit the tags of {service_name}"), index, password);
+        Ok(())
+    }
+    pub fn get_tags(&self, service_name: &str) -> Result<Vec<String>, String> {
+        Ok(self
+            .passwords
+            .iter()
+            .find(|p| p.service == service_name)
+            .ok_or("couldn't find service")?
+            .metadata
+            .get("tags")
+            .map(|tags| tags.split(',').map(|tag| tag.to_string()).collect())
+            .unwrap_or_default())
+    }
+    /// The services that have `tag`
+    pub fn services_with_tag(&self, tag: &str) -> Vec<String> {
+        self.get_services()
+            .into_iter()
+            .filter(|service| {
+                self.get_tags(service)
+                    .is_ok_and(|tags| tags.iter().any(|t| t == tag))
+            })
+            .collect()
+    }
+    pub fn get_settings(&self) -> &VaultSettings {
+        &self.settings
+    }
+    /// Changes the format and KDF parameters the vault is saved with
+    pub fn set_settings(&mut self, settings: VaultSettings) {
+        for password in self.passwords.iter_mut() {
+            password.kdf = settings.kdf;
+        }
+        self.settings = settings;
+    }
+    /// Changes the master password the vault is saved with
+    pub fn set_master_password(&mut self, master_password: SecretString) {
+        for password in self.passwords.iter_mut() {
+            password.key = master_password.clone();
+        }
+        self.master_password = master_password;
+    }
+    /// Checks if `password` is the master password of the vault
+    pub fn is_master_password(&self, password: &SecretString) -> bool {
+        password.expose_secret() == self.master_password.expose_secret()
+    }
+    /// Generates the current OTP code of a service. HOTP counters change on every use, the new
+    /// counter is written to the vault right away and can't be undone or reverted (a code can
+    /// only be used once)
+    pub fn otp_code(&mut self, service_name: &str) -> Result<String, String> {
+        let index = self.index_of(service_name).ok_or("couldn't find service")?;
+        let mut otp = self.get_otp(service_name)?;
+        let code = otp.generate();
+        if matches!(otp.kind, OtpKind::Hotp { .. }) {
+            let salt = self.passwords[index].salt.clone();
+            let copies = self
+                .passwords
+                .iter_mut()
+                .chain(self.saved.iter_mut())
+                .chain(self.history.passwords_mut());
+            for password in copies.filter(|p| p.salt == salt) {
+                password.advance_hotp(&otp);
+            }
+            // an entry that isn't saved yet gets the counter when it's saved
+            if let Some(saved) = self.saved.iter().position(|p| p.salt == salt) {
+                self.saved[saved].save_otp(&self.directory_name, saved)?;
+            }
+        }
+        Ok(code)
+    }
 }
 
 pub fn print_wrapper(data: String) {
@@ -363,7 +1417,11 @@ pub fn get_master_password(dir_name: &str) -> Result<String, std::io::Error> {
 
 /// Initializes the directories and makes the master password
 /// its used to create new directories for the password manager to manage
-pub fn initialize_directory(name: &str, master_password: &str) -> Result<(), String> {
+pub fn initialize_directory(
+    name: &str,
+    master_password: &str,
+    settings: &VaultSettings,
+) -> Result<(), String> {
     fs::create_dir(name).map_err(|err| format!("Error when creating directory: {err}"))?;
     fs::create_dir(format!("{name}/passwords"))
         .map_err(|err| format!("Error when creating directory: {err}"))?;
@@ -371,7 +1429,67 @@ pub fn initialize_directory(name: &str, master_password: &str) -> Result<(), Str
         .map_err(|err| format!("Error when creating directory: {err}"))?;
     fs::create_dir(format!("{name}/salts"))
         .map_err(|err| format!("Error when creating directory: {err}"))?;
-    create_master_password(master_password, name);
+    fs::create_dir(format!("{name}/otps"))
+        .map_err(|err| format!("Error when creating directory: {err}"))?;
+    fs::create_dir(format!("{name}/metadata"))
+        .map_err(|err| format!("Error when creating directory: {err}"))?;
+    fs::create_dir(format!("{name}/pending"))
+        .map_err(|err| format!("Error when creating directory: {err}"))?;
+    settings.write(name)?;
+    create_master_password(master_password, name, &settings.kdf);
+    Ok(())
+}
+
+/// Decrypts every entry of the vault in `directory` without keeping anything, returns how many
+/// entries there are and what's wrong with the ones that can't be read
+pub fn verify_vault(
+    directory: &str,
+    master_password: &SecretString,
+) -> Result<(usize, Vec<String>), String> {
+    let info = VaultInfo::read(directory)?;
+    let mut problems = vec![];
+    let mut services: Vec<String> = vec![];
+    for index in 0..info.entries {
+        let result = Password::load(
+            directory,
+            index,
+            master_password.expose_secret(),
+            info.settings.kdf,
+        )
+        .and_then(|mut password| {
+            password.decrypt()?;
+            password.get_otp()?;
+            Ok(password.service)
+        });
+        match result {
+            Ok(service) if services.contains(&service) => {
+                problems.push(format!("entry {index}: {service} is in the vault twice"))
+            }
+            Ok(service) => services.push(service),
+            Err(error) => problems.push(format!("entry {index}: {error}")),
+        }
+    }
+    Ok((info.entries, problems))
+}
+
+/// Fails with the first file or directory in the vault at `dir_name` that can't be opened (like
+/// one without read permission), so that isn't mistaken for a corrupt vault
+pub fn check_readable(dir_name: &str) -> Result<(), String> {
+    let mut paths = vec![PathBuf::from(dir_name)];
+    while let Some(path) = paths.pop() {
+        if path.is_dir() {
+            let entries = fs::read_dir(&path)
+                .map_err(|err| format!("Failed to read {}, Error: {err}", path.display()))?;
+            paths.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path()),
+            );
+        } else {
+            fs::File::open(&path)
+                .map_err(|err| format!("Failed to read {}, Error: {err}", path.display()))?;
+        }
+    }
     Ok(())
 }
 
@@ -389,11 +1507,9 @@ pub fn verify_directory(dir_name: &str) -> bool {
         }
     }
     for dir in dirs.iter() {
-        let meta = fs::metadata(dir);
-        if meta.is_err() {
-            return false;
-        } else if !meta.unwrap().is_dir() {
-            return false;
+        match fs::metadata(dir) {
+            Ok(meta) if meta.is_dir() => {}
+            _ => return false,
         }
     }
     let salt_count = fs::read_dir(&dirs[0]).unwrap().count();
@@ -405,14 +1521,186 @@ pub fn verify_directory(dir_name: &str) -> bool {
     true
 }
 
-fn create_master_password(master_password: &str, dir_name: &str) {
+fn create_master_password(master_password: &str, dir_name: &str, kdf: &KdfParams) {
     let salt = generate_salt(&mut OsRng).unwrap();
     let _ = fs::write(
         format!("{dir_name}/master_password"),
-        hash(master_password.as_bytes(), &salt).unwrap(),
+        hash(master_password.as_bytes(), &salt, kdf).unwrap(),
     );
 }
 
+/// Reads a file that doesn't have to exist
+fn read_optional(location: &str) -> Result<Option<String>, String> {
+    if !fs::exists(location)
+        .map_err(|err| format!("Failed to check if {location} exists: {err}"))?
+    {
+        return Ok(None);
+    }
+    fs::read_to_string(location)
+        .map(Some)
+        .map_err(|err| format!("Failed to read {location}, Error: {err}"))
+}
+
 fn sleep(duration_millis: u64) {
     std::thread::sleep(Duration::from_millis(duration_millis));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otp::Algorithm;
+
+    /// Makes an empty vault in the system temp directory with a fast KDF
+    fn vault(name: &str) -> PasswordArray {
+        let directory = std::env::temp_dir()
+            .join(format!("nspm_{name}_{}", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let _ = fs::remove_dir_all(&directory);
+        let settings = VaultSettings {
+            kdf: KdfParams {
+                memory_cost: 64,
+                time_cost: 1,
+                ..KdfParams::default()
+            },
+            ..VaultSettings::default()
+        };
+        initialize_directory(&directory, "master", &settings).unwrap();
+        let mut password_array = PasswordArray::new(SecretString::from("master"), directory);
+        password_array.load(false).unwrap();
+        password_array
+    }
+
+    fn reload(password_array: &PasswordArray) -> PasswordArray {
+        let mut reloaded = PasswordArray::new(
+            SecretString::from("master"),
+            password_array.directory_name.clone(),
+        );
+        reloaded.load(false).unwrap();
+        reloaded
+    }
+
+    fn hotp(counter: u64) -> Otp {
+        Otp {
+            kind: OtpKind::Hotp { counter },
+            secret: b"12345678901234567890".to_vec(),
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+            issuer: String::new(),
+            account: String::new(),
+        }
+    }
+
+    #[test]
+    fn hotp_counter_is_saved_right_away() {
+        let mut password_array = vault("hotp_counter");
+        password_array
+            .add_password(String::from("a"), SecretString::from("old"))
+            .unwrap();
+        password_array.set_otp(String::from("a"), &hotp(0)).unwrap();
+        password_array.save(false).unwrap();
+
+        password_array
+            .edit_password(String::from("a"), SecretString::from("new"))
+            .unwrap();
+        assert_eq!(password_array.otp_code("a").unwrap(), "755224");
+        assert_eq!(password_array.otp_code("a").unwrap(), "287082");
+        // the counter isn't a change, undoing the edit keeps it
+        assert_eq!(password_array.changes().len(), 1);
+        password_array.undo().unwrap();
+        assert!(!password_array.has_unsaved_changes());
+        assert_eq!(password_array.get_otp("a").unwrap().kind, hotp(2).kind);
+
+        let mut reloaded = reload(&password_array);
+        assert_eq!(reloaded.get_otp("a").unwrap().kind, hotp(2).kind);
+        assert_eq!(reloaded.get_password("a").unwrap().expose_secret(), "old");
+        assert_eq!(reloaded.otp_code("a").unwrap(), "359152");
+        let _ = fs::remove_dir_all(&password_array.directory_name);
+    }
+
+    #[test]
+    fn hotp_counter_of_an_unsaved_entry_is_saved_with_it() {
+        let mut password_array = vault("hotp_unsaved");
+        password_array
+            .add_password(String::from("a"), SecretString::from("password"))
+            .unwrap();
+        password_array.set_otp(String::from("a"), &hotp(5)).unwrap();
+        password_array.otp_code("a").unwrap();
+        assert!(reload(&password_array).get_services().is_empty());
+        password_array.save(false).unwrap();
+        assert_eq!(
+            reload(&password_array).get_otp("a").unwrap().kind,
+            hotp(6).kind
+        );
+        let _ = fs::remove_dir_all(&password_array.directory_name);
+    }
+
+    #[test]
+    fn lock_forgets_the_keys_until_unlock() {
+        let mut password_array = vault("lock");
+        password_array
+            .add_password(String::from("a"), SecretString::from("old"))
+            .unwrap();
+        password_array.save(false).unwrap();
+        password_array
+            .edit_password(String::from("a"), SecretString::from("new"))
+            .unwrap();
+
+        password_array.lock();
+        let mut entries = password_array
+            .passwords
+            .iter()
+            .chain(password_array.saved.iter());
+        assert!(entries.all(|password| password.key.expose_secret().is_empty()));
+        assert!(password_array.master_password.expose_secret().is_empty());
+
+        password_array.unlock(SecretString::from("master"));
+        password_array.undo().unwrap();
+        password_array.redo().unwrap();
+        password_array.save(false).unwrap();
+        let reloaded = reload(&password_array);
+        assert_eq!(reloaded.get_password("a").unwrap().expose_secret(), "new");
+    }
+
+    #[test]
+    fn rename_is_a_change_that_can_be_reverted() {
+        let mut password_array = vault("rename");
+        for service in ["a", "b"] {
+            password_array
+                .add_password(service.to_string(), SecretString::from("password"))
+                .unwrap();
+        }
+        password_array.save(false).unwrap();
+        assert!(
+            password_array
+                .rename_service("a", String::from("b"))
+                .is_err()
+        );
+        assert!(
+            password_array
+                .rename_service("c", String::from("d"))
+                .is_err()
+        );
+
+        password_array
+            .rename_service("a", String::from("c"))
+            .unwrap();
+        assert_eq!(password_array.get_services(), ["c", "b"]);
+        let changes = password_array.changes();
+        assert_eq!(
+            changes,
+            [Change::Renamed {
+                from: String::from("a"),
+                to: String::from("c"),
+                fields: vec![],
+            }]
+        );
+        assert_eq!(changes[0].to_string(), "~ a → c");
+
+        password_array.revert(&changes[0]).unwrap();
+        assert_eq!(password_array.get_services(), ["a", "b"]);
+        assert!(!password_array.has_unsaved_changes());
+        let _ = fs::remove_dir_all(&password_array.directory_name);
+    }
+}
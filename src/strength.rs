@@ -0,0 +1,995 @@
+//! A password strength estimator based on [zxcvbn](https://github.com/dropbox/zxcvbn)
+//!
+//! The password is split into the parts an attacker would guess (common passwords, words,
+//! keyboard patterns, repeats, sequences, dates) and the cheapest way to cover the whole
+//! password decides how many guesses it takes
+
+use crate::generator::Wordlist;
+use std::{
+    collections::HashMap,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const COMMON_PASSWORDS: &str = include_str!("wordlists/common_passwords.txt");
+
+/// Longer passwords only have the start analyzed, the rest counts as random
+const MAX_ANALYZED_LENGTH: usize = 100;
+const MAX_WORD_LENGTH: usize = 20;
+/// Every random character counts as 10 guesses like in zxcvbn
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+const KEYBOARD_AVERAGE_DEGREE: f64 = 4.6;
+const MAX_L33T_COMBINATIONS: usize = 64;
+
+const L33T_TABLE: [(char, &[char]); 12] = [
+    ('a', &['4', '@']),
+    ('b', &['8']),
+    ('c', &['(', '{', '[', '<']),
+    ('e', &['3']),
+    ('g', &['6', '9']),
+    ('i', &['1', '!', '|']),
+    ('l', &['1', '|', '7']),
+    ('o', &['0']),
+    ('s', &['$', '5']),
+    ('t', &['+', '7']),
+    ('x', &['%']),
+    ('z', &['2']),
+];
+
+/// (unshifted, shifted) rows of a qwerty keyboard, the first key of every row except the top
+/// one sits between the first two keys of the row above it
+const QWERTY: [(&str, &str); 4] = [
+    ("`1234567890-=", "~!@#$%^&*()_+"),
+    ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+    ("asdfghjkl;'", "ASDFGHJKL:\""),
+    ("zxcvbnm,./", "ZXCVBNM<>?"),
+];
+
+/// Guesses per second of different attacks and what they're called
+const ATTACKS: [(&str, f64); 4] = [
+    ("online attack (100/hour)", 100.0 / 3600.0),
+    ("online attack (10/second)", 10.0),
+    ("offline attack, slow hash (10k/second)", 1e4),
+    ("offline attack, fast hash (10B/second)", 1e10),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Dictionary {
+        dictionary: &'static str,
+        rank: usize,
+        reversed: bool,
+        l33t: bool,
+    },
+    Spatial {
+        turns: usize,
+        shifted: usize,
+    },
+    Repeat {
+        base: String,
+        count: usize,
+    },
+    Sequence {
+        ascending: bool,
+    },
+    Date,
+    Year,
+    Bruteforce,
+}
+
+/// A part of the password and how many guesses it takes
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub pattern: Pattern,
+    /// Index of the first character
+    pub start: usize,
+    /// Index after the last character
+    pub end: usize,
+    pub token: String,
+    pub guesses: f64,
+}
+
+/// The result of [estimate]
+#[derive(Debug, Clone)]
+pub struct Strength {
+    pub guesses_log10: f64,
+    /// 0 (too guessable) to 4 (very unguessable)
+    pub score: u8,
+    /// The parts the password was split into
+    pub sequence: Vec<Match>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl Strength {
+    /// log2 of the guesses
+    pub fn entropy(&self) -> f64 {
+        self.guesses_log10 / 2_f64.log10()
+    }
+    /// How long every attack in [ATTACKS] takes
+    pub fn crack_times(&self) -> Vec<(&'static str, String)> {
+        ATTACKS
+            .iter()
+            .map(|(name, rate)| {
+                let seconds_log10 = self.guesses_log10 - rate.log10();
+                (*name, display_time(seconds_log10))
+            })
+            .collect()
+    }
+    /// Only a score of 4 holds up against an offline attack on a slow hash
+    pub fn is_strong(&self) -> bool {
+        self.score >= 4
+    }
+    pub fn score_name(&self) -> &'static str {
+        match self.score {
+            0 => "very weak",
+            1 => "weak",
+            2 => "fair",
+            3 => "good",
+            _ => "strong",
+        }
+    }
+}
+
+/// Estimates how many guesses it takes to crack `password`
+///
+/// # Example
+/// ```
+/// use nspm::strength::estimate;
+/// assert!(!estimate("Aaaa1111!!!!Password").is_strong());
+/// assert_eq!(estimate("qwertyuiop").score, 0);
+/// assert!(estimate("correct-horse-battery-staple-violin-ocean").is_strong());
+/// ```
+pub fn estimate(password: &str) -> Strength {
+    let characters: Vec<char> = password.chars().collect();
+    let analyzed = &characters[..characters.len().min(MAX_ANALYZED_LENGTH)];
+    let (mut guesses_log10, sequence) = most_guessable_sequence(analyzed);
+    guesses_log10 += (characters.len() - analyzed.len()) as f64 * BRUTEFORCE_CARDINALITY.log10();
+    let score = match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    };
+    let (warning, suggestions) = feedback(score, &sequence);
+    Strength {
+        guesses_log10,
+        score,
+        sequence,
+        warning,
+        suggestions,
+    }
+}
+
+/// The common passwords ranked from most to least common, and "english": the EFF large wordlist
+/// the passphrases are made of. That one isn't sorted by how common the words are, so every word
+/// has the same rank, the size of the list
+fn dictionaries() -> &'static [(&'static str, HashMap<String, usize>)] {
+    static DICTIONARIES: OnceLock<Vec<(&'static str, HashMap<String, usize>)>> = OnceLock::new();
+    DICTIONARIES.get_or_init(|| {
+        let mut passwords = HashMap::new();
+        for (rank, password) in COMMON_PASSWORDS.lines().enumerate() {
+            passwords.entry(password.to_lowercase()).or_insert(rank + 1);
+        }
+        let words = Wordlist::Large.words();
+        let english = words
+            .iter()
+            .map(|word| (word.to_lowercase(), words.len()))
+            .collect();
+        vec![("passwords", passwords), ("english", english)]
+    })
+}
+
+/// log10 product of the guesses, where the last part starts and its index in the matches (None
+/// for random characters)
+type Step = (f64, usize, Option<usize>);
+
+/// Finds the cheapest way to cover the password with matches (and random characters between
+/// them), returns log10 of the guesses and the matches
+fn most_guessable_sequence(password: &[char]) -> (f64, Vec<Match>) {
+    let n = password.len();
+    if n == 0 {
+        return (0.0, vec![]);
+    }
+    let matches = omnimatch(password);
+    // best[k][l]: lowest log10 product of guesses covering the first k characters with l parts
+    let mut best: Vec<HashMap<usize, Step>> = vec![HashMap::new(); n + 1];
+    best[0].insert(0, (0.0, 0, None));
+    for k in 1..=n {
+        let mut candidates = vec![];
+        for (index, m) in matches.iter().enumerate().filter(|(_, m)| m.end == k) {
+            candidates.push((m.start, m.guesses.log10(), Some(index)));
+        }
+        for start in 0..k {
+            candidates.push((start, bruteforce_guesses(k - start).log10(), None));
+        }
+        for (start, guesses, index) in candidates {
+            let previous: Vec<(usize, f64)> = best[start]
+                .iter()
+                // random characters after random characters are one part like in zxcvbn
+                .filter(|(l, (_, _, previous))| index.is_some() || **l == 0 || previous.is_some())
+                .map(|(l, (g, _, _))| (*l, *g))
+                .collect();
+            for (l, previous_guesses) in previous {
+                let total = previous_guesses + guesses;
+                let entry = best[k].entry(l + 1).or_insert((f64::INFINITY, 0, None));
+                if total < entry.0 {
+                    *entry = (total, start, index);
+                }
+            }
+        }
+    }
+    // unlike zxcvbn there's no minimum for every extra part, otherwise anything made of a few
+    // predictable parts (like `Aaaa1111!!!!Password`) counts as unguessable
+    let mut most_guessable = (f64::INFINITY, vec![]);
+    for (parts, (product, _, _)) in &best[n] {
+        let mut sequence = vec![];
+        let (mut k, mut l) = (n, *parts);
+        while k > 0 {
+            let (_, start, index) = best[k][&l];
+            sequence.push(match index {
+                Some(index) => matches[index].clone(),
+                None => Match {
+                    pattern: Pattern::Bruteforce,
+                    start,
+                    end: k,
+                    token: password[start..k].iter().collect(),
+                    guesses: bruteforce_guesses(k - start),
+                },
+            });
+            (k, l) = (start, l - 1);
+        }
+        sequence.reverse();
+        let total = orderings_log10(&sequence) + product;
+        if total < most_guessable.0 {
+            most_guessable = (total, sequence);
+        }
+    }
+    most_guessable
+}
+
+/// log10 of the orders the parts could be in. Parts of the same kind (like the 3 repeats of
+/// `Aaaa1111!!!!Password`) don't count as different orders, the guesses of one already cover
+/// what the others could be
+fn orderings_log10(sequence: &[Match]) -> f64 {
+    let mut kinds: HashMap<std::mem::Discriminant<Pattern>, usize> = HashMap::new();
+    for m in sequence {
+        *kinds.entry(std::mem::discriminant(&m.pattern)).or_default() += 1;
+    }
+    log10_factorial(sequence.len()) - kinds.values().map(|n| log10_factorial(*n)).sum::<f64>()
+}
+
+fn omnimatch(password: &[char]) -> Vec<Match> {
+    let mut matches = dictionary_matches(password);
+    matches.append(&mut reversed_dictionary_matches(password));
+    matches.append(&mut l33t_matches(password));
+    matches.append(&mut spatial_matches(password));
+    matches.append(&mut repeat_matches(password));
+    matches.append(&mut sequence_matches(password));
+    matches.append(&mut year_matches(password));
+    matches.append(&mut date_matches(password));
+    for m in matches.iter_mut() {
+        let minimum = if m.end - m.start == 1 {
+            MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+        } else {
+            MIN_SUBMATCH_GUESSES_MULTI_CHAR
+        };
+        m.guesses = m.guesses.max(minimum);
+    }
+    matches
+}
+
+fn bruteforce_guesses(length: usize) -> f64 {
+    let minimum = if length == 1 {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+    } else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR
+    };
+    BRUTEFORCE_CARDINALITY
+        .powi(length as i32)
+        .max(minimum + 1.0)
+}
+
+fn dictionary_matches(password: &[char]) -> Vec<Match> {
+    let lowercase: Vec<char> = password.iter().map(|c| lowercase(*c)).collect();
+    let mut matches = vec![];
+    for (name, dictionary) in dictionaries() {
+        for start in 0..password.len() {
+            for end in start + 1..=password.len().min(start + MAX_WORD_LENGTH) {
+                let word: String = lowercase[start..end].iter().collect();
+                if let Some(rank) = dictionary.get(&word) {
+                    let token: String = password[start..end].iter().collect();
+                    matches.push(Match {
+                        guesses: *rank as f64 * uppercase_variations(&token),
+                        pattern: Pattern::Dictionary {
+                            dictionary: name,
+                            rank: *rank,
+                            reversed: false,
+                            l33t: false,
+                        },
+                        start,
+                        end,
+                        token,
+                    });
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn reversed_dictionary_matches(password: &[char]) -> Vec<Match> {
+    let reversed: Vec<char> = password.iter().rev().copied().collect();
+    let n = password.len();
+    dictionary_matches(&reversed)
+        .into_iter()
+        .map(|mut m| {
+            (m.start, m.end) = (n - m.end, n - m.start);
+            m.token = password[m.start..m.end].iter().collect();
+            m.guesses *= 2.0;
+            if let Pattern::Dictionary { reversed, .. } = &mut m.pattern {
+                *reversed = true;
+            }
+            m
+        })
+        // palindromes are already found the normal way
+        .filter(|m| m.token.chars().ne(m.token.chars().rev()))
+        .collect()
+}
+
+fn l33t_matches(password: &[char]) -> Vec<Match> {
+    // every l33t character in the password with the letters it could stand for
+    let mut substitutions: Vec<(char, Vec<char>)> = vec![];
+    for character in password {
+        let letters: Vec<char> = L33T_TABLE
+            .iter()
+            .filter(|(_, subs)| subs.contains(character))
+            .map(|(letter, _)| *letter)
+            .collect();
+        if !letters.is_empty() && !substitutions.iter().any(|(c, _)| c == character) {
+            substitutions.push((*character, letters));
+        }
+    }
+    if substitutions.is_empty() {
+        return vec![];
+    }
+    let mut combinations: Vec<Vec<(char, char)>> = vec![vec![]];
+    for (character, letters) in &substitutions {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                letters.iter().map(move |letter| {
+                    let mut combination = combination.clone();
+                    combination.push((*character, *letter));
+                    combination
+                })
+            })
+            .take(MAX_L33T_COMBINATIONS)
+            .collect();
+    }
+    let mut matches = vec![];
+    for combination in combinations {
+        let translated: Vec<char> = password
+            .iter()
+            .map(|c| {
+                combination
+                    .iter()
+                    .find(|(sub, _)| sub == c)
+                    .map_or(*c, |(_, letter)| *letter)
+            })
+            .collect();
+        for mut m in dictionary_matches(&translated) {
+            let token: Vec<char> = password[m.start..m.end].to_vec();
+            let used: Vec<&(char, char)> = combination
+                .iter()
+                .filter(|(sub, _)| token.contains(sub))
+                .collect();
+            if used.is_empty() {
+                continue;
+            }
+            let mut variations = 1.0;
+            for (sub, letter) in used {
+                let subbed = token.iter().filter(|c| *c == sub).count();
+                let unsubbed = token.iter().filter(|c| lowercase(**c) == *letter).count();
+                variations *= if unsubbed == 0 {
+                    2.0
+                } else {
+                    (1..=subbed.min(unsubbed))
+                        .map(|i| n_choose_k(subbed + unsubbed, i))
+                        .sum()
+                };
+            }
+            m.token = token.iter().collect();
+            m.guesses *= variations;
+            if let Pattern::Dictionary { l33t, .. } = &mut m.pattern {
+                *l33t = true;
+            }
+            matches.push(m);
+        }
+    }
+    matches
+}
+
+/// How many ways the capitalization of a word could be guessed
+fn uppercase_variations(token: &str) -> f64 {
+    let upper = token.chars().filter(|c| c.is_uppercase()).count();
+    let lower = token.chars().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_upper = token.chars().next().is_some_and(|c| c.is_uppercase()) && upper == 1;
+    let last_upper = token.chars().last().is_some_and(|c| c.is_uppercase()) && upper == 1;
+    if first_upper || last_upper || lower == 0 {
+        return 2.0;
+    }
+    (1..=upper.min(lower))
+        .map(|i| n_choose_k(upper + lower, i))
+        .sum()
+}
+
+fn keyboard_position(character: char) -> Option<(i32, i32, bool)> {
+    for (row, (unshifted, shifted)) in QWERTY.iter().enumerate() {
+        let offset = if row == 0 { 0 } else { 1 };
+        if let Some(x) = unshifted.chars().position(|c| c == character) {
+            return Some((x as i32 + offset, row as i32, false));
+        }
+        if let Some(x) = shifted.chars().position(|c| c == character) {
+            return Some((x as i32 + offset, row as i32, true));
+        }
+    }
+    None
+}
+
+/// Which of the 6 neighbours `b` is of `a` (None if they aren't next to each other)
+fn keyboard_direction(a: char, b: char) -> Option<usize> {
+    let ((ax, ay, _), (bx, by, _)) = (keyboard_position(a)?, keyboard_position(b)?);
+    [(-1, 0), (0, -1), (1, -1), (1, 0), (0, 1), (-1, 1)]
+        .iter()
+        .position(|(dx, dy)| ax + dx == bx && ay + dy == by)
+}
+
+fn spatial_matches(password: &[char]) -> Vec<Match> {
+    let keys: usize = QWERTY.iter().map(|(row, _)| row.len()).sum();
+    let mut matches = vec![];
+    let mut start = 0;
+    while start + 2 < password.len() {
+        let (mut end, mut turns, mut last_direction) = (start + 1, 0, None);
+        while end < password.len() {
+            match keyboard_direction(password[end - 1], password[end]) {
+                Some(direction) => {
+                    if last_direction != Some(direction) {
+                        turns += 1;
+                        last_direction = Some(direction);
+                    }
+                    end += 1;
+                }
+                None => break,
+            }
+        }
+        let length = end - start;
+        if length < 3 {
+            start += 1;
+            continue;
+        }
+        let shifted = password[start..end]
+            .iter()
+            .filter(|c| keyboard_position(**c).is_some_and(|(_, _, s)| s))
+            .count();
+        let mut guesses = 0.0;
+        for i in 2..=length {
+            for j in 1..=turns.min(i - 1) {
+                guesses +=
+                    n_choose_k(i - 1, j - 1) * keys as f64 * KEYBOARD_AVERAGE_DEGREE.powi(j as i32);
+            }
+        }
+        let unshifted = length - shifted;
+        if shifted > 0 {
+            guesses *= if unshifted == 0 {
+                2.0
+            } else {
+                (1..=shifted.min(unshifted))
+                    .map(|i| n_choose_k(length, i))
+                    .sum()
+            };
+        }
+        matches.push(Match {
+            pattern: Pattern::Spatial { turns, shifted },
+            start,
+            end,
+            token: password[start..end].iter().collect(),
+            guesses,
+        });
+        start = end - 1;
+    }
+    matches
+}
+
+/// Repeats ignore case (`Aaaa` is `a` 4 times with some capitalization)
+fn repeat_matches(password: &[char]) -> Vec<Match> {
+    let lowercase: Vec<char> = password.iter().map(|c| lowercase(*c)).collect();
+    let mut matches = vec![];
+    let mut start = 0;
+    while start < password.len() {
+        // (base length, count) that covers the most characters
+        let mut best = (0, 0);
+        for base_length in 1..=(password.len() - start) / 2 {
+            let base = &lowercase[start..start + base_length];
+            let mut count = 1;
+            while start + (count + 1) * base_length <= password.len()
+                && &lowercase[start + count * base_length..start + (count + 1) * base_length]
+                    == base
+            {
+                count += 1;
+            }
+            if count >= 2 && count * base_length > best.0 * best.1 {
+                best = (base_length, count);
+            }
+        }
+        let (base_length, count) = best;
+        if count < 2 {
+            start += 1;
+            continue;
+        }
+        let end = start + base_length * count;
+        let base: String = lowercase[start..start + base_length].iter().collect();
+        let (base_guesses_log10, _) =
+            most_guessable_sequence(&lowercase[start..start + base_length]);
+        let token: String = password[start..end].iter().collect();
+        matches.push(Match {
+            pattern: Pattern::Repeat { base, count },
+            start,
+            end,
+            guesses: 10_f64.powf(base_guesses_log10) * count as f64 * uppercase_variations(&token),
+            token,
+        });
+        start = end;
+    }
+    matches
+}
+
+fn sequence_matches(password: &[char]) -> Vec<Match> {
+    let class = |c: char| {
+        if c.is_ascii_lowercase() {
+            1
+        } else if c.is_ascii_uppercase() {
+            2
+        } else if c.is_ascii_digit() {
+            3
+        } else {
+            0
+        }
+    };
+    let mut matches = vec![];
+    let mut start = 0;
+    while start + 2 < password.len() {
+        let (first, second) = (password[start], password[start + 1]);
+        let delta = second as i32 - first as i32;
+        if class(first) == 0 || class(first) != class(second) || delta == 0 || delta.abs() > 5 {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 2;
+        while end < password.len()
+            && class(password[end]) == class(first)
+            && password[end] as i32 - password[end - 1] as i32 == delta
+        {
+            end += 1;
+        }
+        if end - start < 3 {
+            start += 1;
+            continue;
+        }
+        let base = if "aAzZ019".contains(first) {
+            4.0
+        } else if first.is_ascii_digit() {
+            10.0
+        } else {
+            26.0
+        };
+        let ascending = delta > 0;
+        matches.push(Match {
+            pattern: Pattern::Sequence { ascending },
+            start,
+            end,
+            token: password[start..end].iter().collect(),
+            guesses: base * (end - start) as f64 * if ascending { 1.0 } else { 2.0 },
+        });
+        start = end - 1;
+    }
+    matches
+}
+
+fn current_year() -> i32 {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    1970 + (seconds / 31_556_952) as i32
+}
+
+fn year_guesses(year: i32) -> f64 {
+    ((year - current_year()).abs() as f64).max(MIN_YEAR_SPACE)
+}
+
+fn year_matches(password: &[char]) -> Vec<Match> {
+    let mut matches = vec![];
+    for start in 0..password.len().saturating_sub(3) {
+        let token: String = password[start..start + 4].iter().collect();
+        if (token.starts_with("19") || token.starts_with("20"))
+            && token.chars().all(|c| c.is_ascii_digit())
+        {
+            matches.push(Match {
+                pattern: Pattern::Year,
+                start,
+                end: start + 4,
+                guesses: year_guesses(token.parse().unwrap_or_default()),
+                token,
+            });
+        }
+    }
+    matches
+}
+
+/// Turns (year, month, day) candidates into a full year if the date could be real
+fn valid_date(year: u32, month: u32, day: u32, year_digits: usize) -> Option<i32> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let year = match year_digits {
+        2 if year < 50 => 2000 + year,
+        2 => 1900 + year,
+        4 if (1000..=2050).contains(&year) => year,
+        _ => return None,
+    };
+    Some(year as i32)
+}
+
+/// Tries year first and year last orders with the day and month either way around
+fn date_year(parts: [&str; 3]) -> Option<i32> {
+    let number = |s: &str| s.parse::<u32>().ok();
+    let (a, b, c) = (number(parts[0])?, number(parts[1])?, number(parts[2])?);
+    let candidates = [
+        (a, b, c, parts[0].len()),
+        (c, b, a, parts[2].len()),
+        (c, a, b, parts[2].len()),
+    ];
+    candidates
+        .iter()
+        .find_map(|(year, month, day, digits)| {
+            [(*month, *day), (*day, *month)]
+                .iter()
+                .find_map(|(m, d)| valid_date(*year, *m, *d, *digits))
+        })
+        .filter(|_| parts.iter().all(|p| p.len() <= 4 && !p.is_empty()))
+}
+
+fn date_matches(password: &[char]) -> Vec<Match> {
+    let mut matches = vec![];
+    let n = password.len();
+    // without separators: 4 to 8 digits
+    for start in 0..n {
+        for end in start + 4..=n.min(start + 8) {
+            let token: String = password[start..end].iter().collect();
+            if !token.chars().all(|c| c.is_ascii_digit()) {
+                break;
+            }
+            let mut best: Option<i32> = None;
+            for i in 1..token.len() {
+                for j in i + 1..token.len() {
+                    let parts = [&token[..i], &token[i..j], &token[j..]];
+                    if parts.iter().filter(|p| p.len() > 2).count() > 1 {
+                        continue;
+                    }
+                    if let Some(year) = date_year(parts)
+                        && best.is_none_or(|b| year_guesses(year) < year_guesses(b))
+                    {
+                        best = Some(year);
+                    }
+                }
+            }
+            if let Some(year) = best {
+                matches.push(Match {
+                    pattern: Pattern::Date,
+                    start,
+                    end,
+                    guesses: 365.0 * year_guesses(year),
+                    token,
+                });
+            }
+        }
+    }
+    // with separators like 12/05/1990 or 1990-05-12
+    for start in 0..n {
+        for end in start + 6..=n.min(start + 10) {
+            let token: String = password[start..end].iter().collect();
+            let Some(separator) = token.chars().find(|c| !c.is_ascii_digit()) else {
+                continue;
+            };
+            if !" /\\_.-".contains(separator) {
+                continue;
+            }
+            let parts: Vec<&str> = token.split(separator).collect();
+            if parts.len() != 3 || parts.iter().any(|p| !p.chars().all(|c| c.is_ascii_digit())) {
+                continue;
+            }
+            if let Some(year) = date_year([parts[0], parts[1], parts[2]]) {
+                matches.push(Match {
+                    pattern: Pattern::Date,
+                    start,
+                    end,
+                    guesses: 365.0 * year_guesses(year) * 4.0,
+                    token,
+                });
+            }
+        }
+    }
+    matches
+}
+
+fn feedback(score: u8, sequence: &[Match]) -> (Option<String>, Vec<String>) {
+    if sequence.is_empty() {
+        return (
+            None,
+            vec![
+                String::from("Use a few words, avoid common phrases"),
+                String::from("No need for symbols, digits, or uppercase letters"),
+            ],
+        );
+    }
+    if score > 3 {
+        return (None, vec![]);
+    }
+    let longest = sequence
+        .iter()
+        .max_by_key(|m| m.token.chars().count())
+        .unwrap();
+    let mut suggestions = vec![String::from(
+        "Add another word or two. Uncommon words are better",
+    )];
+    let warning = match &longest.pattern {
+        Pattern::Dictionary {
+            dictionary,
+            rank,
+            reversed,
+            l33t,
+        } => {
+            let first_upper = longest
+                .token
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_uppercase());
+            if longest.token.chars().all(|c| !c.is_lowercase()) && first_upper {
+                suggestions.push(String::from(
+                    "All-uppercase is almost as easy to guess as all-lowercase",
+                ));
+            } else if first_upper {
+                suggestions.push(String::from("Capitalization doesn't help very much"));
+            }
+            if *reversed && longest.token.chars().count() >= 4 {
+                suggestions.push(String::from("Reversed words aren't much harder to guess"));
+            }
+            if *l33t {
+                suggestions.push(String::from(
+                    "Predictable substitutions like '@' instead of 'a' don't help very much",
+                ));
+            }
+            match *dictionary {
+                "passwords" if sequence.len() > 1 => {
+                    Some("This is similar to a commonly used password")
+                }
+                "passwords" if *rank <= 10 => Some("This is a top-10 common password"),
+                "passwords" if *rank <= 100 => Some("This is a top-100 common password"),
+                "passwords" => Some("This is a very common password"),
+                _ if sequence.len() == 1 => Some("A word by itself is easy to guess"),
+                _ => None,
+            }
+        }
+        Pattern::Spatial { turns, .. } => {
+            suggestions.push(String::from(
+                "Use a longer keyboard pattern with more turns",
+            ));
+            if *turns == 1 {
+                Some("Straight rows of keys are easy to guess")
+            } else {
+                Some("Short keyboard patterns are easy to guess")
+            }
+        }
+        Pattern::Repeat { base, .. } => {
+            suggestions.push(String::from("Avoid repeated words and characters"));
+            if base.chars().count() == 1 {
+                Some("Repeats like \"aaa\" are easy to guess")
+            } else {
+                Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"")
+            }
+        }
+        Pattern::Sequence { .. } => {
+            suggestions.push(String::from("Avoid sequences"));
+            Some("Sequences like abc or 6543 are easy to guess")
+        }
+        Pattern::Year => {
+            suggestions.push(String::from("Avoid recent years"));
+            suggestions.push(String::from("Avoid years that are associated with you"));
+            Some("Recent years are easy to guess")
+        }
+        Pattern::Date => {
+            suggestions.push(String::from(
+                "Avoid dates and years that are associated with you",
+            ));
+            Some("Dates are often easy to guess")
+        }
+        Pattern::Bruteforce => None,
+    };
+    (warning.map(|w| w.to_string()), suggestions)
+}
+
+fn display_time(seconds_log10: f64) -> String {
+    const UNITS: [(&str, f64); 6] = [
+        ("second", 1.0),
+        ("minute", 60.0),
+        ("hour", 3600.0),
+        ("day", 86400.0),
+        ("month", 86400.0 * 31.0),
+        ("year", 86400.0 * 365.0),
+    ];
+    if seconds_log10 < 0.0 {
+        return String::from("less than a second");
+    }
+    if seconds_log10 >= (86400.0_f64 * 365.0 * 100.0).log10() {
+        return String::from("centuries");
+    }
+    let seconds = 10_f64.powf(seconds_log10);
+    let (unit, size) = UNITS
+        .iter()
+        .rev()
+        .find(|(_, size)| seconds >= *size)
+        .unwrap_or(&UNITS[0]);
+    let amount = (seconds / size).round() as u64;
+    if amount == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{amount} {unit}s")
+    }
+}
+
+fn lowercase(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (0..k).fold(1.0, |result, i| result * (n - i) as f64 / (i + 1) as f64)
+}
+
+fn log10_factorial(n: usize) -> f64 {
+    (2..=n).map(|i| (i as f64).log10()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(password: &str) -> Vec<Pattern> {
+        estimate(password)
+            .sequence
+            .into_iter()
+            .map(|m| m.pattern)
+            .collect()
+    }
+
+    #[test]
+    fn predictable_parts_are_weak() {
+        let strength = estimate("Aaaa1111!!!!Password");
+        assert!(strength.score <= 2);
+        assert!(!strength.is_strong());
+        let repeats = |base: &str| Pattern::Repeat {
+            base: base.to_string(),
+            count: 4,
+        };
+        assert_eq!(
+            patterns("Aaaa1111!!!!Password"),
+            [
+                repeats("a"),
+                repeats("1"),
+                repeats("!"),
+                Pattern::Dictionary {
+                    dictionary: "passwords",
+                    rank: 2,
+                    reversed: false,
+                    l33t: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn passphrase_is_strong() {
+        let passphrase = "unsaved-cranberry-hamper-frosting-outsider-mumbo";
+        assert_eq!(estimate(passphrase).score, 4);
+        let words = patterns(passphrase)
+            .into_iter()
+            .filter(|pattern| {
+                *pattern
+                    == Pattern::Dictionary {
+                        dictionary: "english",
+                        rank: 7776,
+                        reversed: false,
+                        l33t: false,
+                    }
+            })
+            .count();
+        assert_eq!(words, 6);
+    }
+
+    #[test]
+    fn keyboard_walks() {
+        let strength = estimate("asdfghjkl;'");
+        assert!(strength.score <= 1);
+        assert_eq!(
+            patterns("asdfghjkl;'"),
+            [Pattern::Spatial {
+                turns: 1,
+                shifted: 0,
+            }]
+        );
+        assert_eq!(
+            strength.warning.as_deref(),
+            Some("Straight rows of keys are easy to guess")
+        );
+        assert_eq!(
+            patterns("TYUIOP{}"),
+            [Pattern::Spatial {
+                turns: 1,
+                shifted: 8,
+            }]
+        );
+        // qwertyuiop is also a common password, which is cheaper
+        assert_eq!(estimate("qwertyuiop").score, 0);
+    }
+
+    #[test]
+    fn dates() {
+        for date in ["12/05/1990", "1990-05-12", "19900512", "12.5.90"] {
+            let strength = estimate(date);
+            assert!(strength.score <= 1, "{date}");
+            assert_eq!(patterns(date), [Pattern::Date], "{date}");
+        }
+        assert_eq!(patterns("1990"), [Pattern::Year]);
+        assert_ne!(patterns("13/13/1990"), [Pattern::Date]);
+    }
+
+    #[test]
+    fn l33t_substitutions() {
+        for password in ["P@ssw0rd", "p4ssw0rd", "pa$$word"] {
+            let strength = estimate(password);
+            assert_eq!(strength.score, 0, "{password}");
+            assert!(matches!(
+                patterns(password)[..],
+                [Pattern::Dictionary {
+                    dictionary: "passwords",
+                    l33t: true,
+                    ..
+                }]
+            ));
+            assert!(
+                strength
+                    .suggestions
+                    .iter()
+                    .any(|suggestion| suggestion.contains("substitutions"))
+            );
+        }
+    }
+
+    #[test]
+    fn random_characters() {
+        let strength = estimate("x7#Kq9!mZ2$w");
+        assert_eq!(strength.score, 4);
+        assert_eq!(patterns("x7#Kq9!mZ2$w"), [Pattern::Bruteforce]);
+        assert_eq!(estimate("").score, 0);
+    }
+}
@@ -0,0 +1,720 @@
+//! A full-screen interface for a vault (`nspm tui`): the services on the left, the details of the
+//! chosen one on the right and a status bar at the bottom, everything is done with single keys
+
+use crate::{
+    ansi::{CLEAR, Csi, constants::*},
+    breach::BreachDatabase,
+    fuzzy,
+    generator::{Generator, PasswordPolicy},
+    storage::PasswordArray,
+    strength::estimate,
+    ui::{
+        Locked, REVEAL_SECONDS, copy_to_clipboard, details, generate_password,
+        leave_alternate_screen, read_key, wait_for_key,
+    },
+};
+use getch_rs::{Getch, Key};
+use secrecy::{ExposeSecret, SecretString};
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::exit,
+    time::{Duration, Instant},
+};
+
+const KEYS: &str = "a: add  e: edit  n: rename  d: delete  c: copy  g: generate  r: reveal  \
+    /: search  ^z: undo  ^y: redo  s: save  q: quit";
+/// Used when the size of the terminal can't be found
+const DEFAULT_SIZE: (usize, usize) = (80, 24);
+/// The smallest size that can be drawn
+const MIN_SIZE: (usize, usize) = (40, 6);
+
+pub struct Tui<'a> {
+    password_array: &'a mut PasswordArray,
+    modified: &'a mut bool,
+    clip_timeout: u64,
+    breach_database: Option<&'a BreachDatabase>,
+    /// What the list is filtered by (see [fuzzy::score])
+    search: String,
+    /// The services in the list
+    services: Vec<String>,
+    selected: usize,
+    /// The first service in the list that's shown
+    scroll: usize,
+    /// The service whose password is shown and until when, it's hidden again after
+    /// [REVEAL_SECONDS] or when another service is selected
+    revealed: Option<(String, Instant)>,
+    /// Shown instead of the keys until the next key is pressed
+    status: String,
+    /// The size of the terminal at the last draw (it's cleared when the size changes)
+    size: (usize, usize),
+}
+
+impl<'a> Tui<'a> {
+    /// Copied passwords are cleared from the clipboard after `clip_timeout` seconds, new passwords
+    /// are checked against `breach_database`
+    pub fn new(
+        password_array: &'a mut PasswordArray,
+        modified: &'a mut bool,
+        clip_timeout: u64,
+        breach_database: Option<&'a BreachDatabase>,
+    ) -> Self {
+        Self {
+            password_array,
+            modified,
+            clip_timeout,
+            breach_database,
+            search: String::new(),
+            services: vec![],
+            selected: 0,
+            scroll: 0,
+            revealed: None,
+            status: String::new(),
+            size: (0, 0),
+        }
+    }
+
+    /// Runs until q (or escape when nothing is searched for) is
+    /// pressed
+    pub fn interact(&mut self) -> Result<(), Locked> {
+        self.filter();
+        print!("{}", Csi::Hide);
+        let getch = Getch::new();
+        loop {
+            self.draw(None);
+            let timeout = self
+                .revealed
+                .as_ref()
+                .map(|(_, until)| until.saturating_duration_since(Instant::now()));
+            let key = match wait_for_key(&getch, timeout)? {
+                None => {
+                    self.revealed = None;
+                    continue;
+                }
+                Some(Ok(key)) => key,
+                Some(Err(error)) => {
+                    self.status = format!("{RED}{error}{RESET}");
+                    continue;
+                }
+            };
+            self.status.clear();
+            let page = self.size.1.saturating_sub(3) as isize;
+            match key {
+                Key::Esc if !self.search.is_empty() => {
+                    self.search.clear();
+                    self.filter();
+                }
+                Key::Char('q') | Key::Esc
+                    if !*self.modified
+                        || self.confirm(&getch, "Quit without saving? (y/n) ")? =>
+                {
+                    break;
+                }
+                Key::Ctrl('c') => {
+                    drop(getch);
+                    // the changes are thrown away on purpose like when quitting with q
+                    let discarded = self.password_array.discard_journal();
+                    leave_alternate_screen();
+                    if let Err(error) = discarded {
+                        eprintln!("{RED}{error}{RESET}");
+                    }
+                    exit(1)
+                }
+                Key::Up | Key::Char('k') => self.move_by(-1),
+                Key::Down | Key::Char('j') => self.move_by(1),
+                Key::PageUp => self.move_by(-page),
+                Key::PageDown => self.move_by(page),
+                Key::Home => self.move_by(-(self.services.len() as isize)),
+                Key::End => self.move_by(self.services.len() as isize),
+                Key::Char('/') => self.search(&getch)?,
+                Key::Char('a') => self.add(&getch)?,
+                Key::Char('e') => self.edit(&getch)?,
+                Key::Char('n') => self.rename(&getch)?,
+                Key::Char('d') => self.delete(&getch)?,
+                Key::Char('c') => self.copy(),
+                Key::Char('g') => self.generate(&getch)?,
+                Key::Char('r') if self.is_revealed() => self.revealed = None,
+                Key::Char('r') => {
+                    if let Some(service) = self.selected_service() {
+                        self.reveal(service);
+                    }
+                }
+                Key::Ctrl('z') => self.undo(false),
+                Key::Ctrl('y') => self.undo(true),
+                Key::Char('s') => self.save(),
+                _ => {}
+            }
+        }
+        drop(getch);
+        print!("{}", Csi::Show);
+        let _ = io::stdout().flush();
+        Ok(())
+    }
+
+    fn selected_service(&self) -> Option<String> {
+        self.services.get(self.selected).cloned()
+    }
+
+    /// Moves the selection, hiding the revealed password
+    fn move_by(&mut self, amount: isize) {
+        let last = self.services.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + amount).clamp(0, last) as usize;
+        self.revealed = None;
+    }
+
+    /// Shows the password of `service` for [REVEAL_SECONDS]
+    fn reveal(&mut self, service: String) {
+        self.revealed = Some((
+            service,
+            Instant::now() + Duration::from_secs(REVEAL_SECONDS),
+        ));
+    }
+
+    fn is_revealed(&self) -> bool {
+        self.revealed
+            .as_ref()
+            .is_some_and(|(service, _)| self.selected_service().as_ref() == Some(service))
+    }
+
+    /// Fills the list with the services matching the search (the best matches first), keeping
+    /// the selected service selected if it still matches
+    fn filter(&mut self) {
+        let selected = self.selected_service();
+        self.services = if self.search.is_empty() {
+            self.password_array.get_services()
+        } else {
+            let mut matches: Vec<(i64, String)> = self
+                .password_array
+                .get_services()
+                .into_iter()
+                .filter_map(|service| Some((fuzzy::score(&self.search, &service)?.0, service)))
+                .collect();
+            matches.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.cmp(b)));
+            matches.into_iter().map(|(_, service)| service).collect()
+        };
+        self.selected = selected
+            .and_then(|selected| self.services.iter().position(|s| *s == selected))
+            .unwrap_or(0);
+    }
+
+    fn select(&mut self, service: &str) {
+        if let Some(index) = self.services.iter().position(|s| s == service) {
+            self.selected = index;
+        }
+    }
+
+    /// Filters the list as the search is typed, enter keeps the search and escape clears it
+    fn search(&mut self, getch: &Getch) -> Result<(), Locked> {
+        loop {
+            self.draw(Some(&format!("/{}", self.search)));
+            match read_key(getch)? {
+                Ok(Key::Char('\r')) => return Ok(()),
+                Ok(Key::Esc) | Ok(Key::Ctrl('c')) => {
+                    self.search.clear();
+                    self.filter();
+                    return Ok(());
+                }
+                Ok(Key::Backspace) | Ok(Key::Delete) => {
+                    self.search.pop();
+                }
+                Ok(Key::Char(character)) if !character.is_control() => self.search.push(character),
+                _ => continue,
+            }
+            self.filter();
+        }
+    }
+
+    /// Reads a line in the status bar, None when escape (or Ctrl-C) is pressed, `secret` hides what's typed
+    /// and shows how strong it is
+    fn prompt(
+        &mut self,
+        getch: &Getch,
+        label: &str,
+        secret: bool,
+    ) -> Result<Option<String>, Locked> {
+        let mut value = String::new();
+        loop {
+            let shown = if !secret {
+                value.clone()
+            } else if value.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    "{} ({})",
+                    "*".repeat(value.chars().count()),
+                    estimate(&value).score_name()
+                )
+            };
+            self.draw(Some(&format!("{label}{shown}")));
+            match read_key(getch)? {
+                Ok(Key::Char('\r')) => return Ok(Some(value)),
+                Ok(Key::Esc) | Ok(Key::Ctrl('c')) => return Ok(None),
+                Ok(Key::Backspace) | Ok(Key::Delete) => {
+                    value.pop();
+                }
+                Ok(Key::Char(character)) if !character.is_control() => value.push(character),
+                _ => {}
+            }
+        }
+    }
+
+    fn confirm(&mut self, getch: &Getch, question: &str) -> Result<bool, Locked> {
+        self.draw(Some(question));
+        Ok(matches!(
+            read_key(getch)?,
+            Ok(Key::Char('y')) | Ok(Key::Char('Y'))
+        ))
+    }
+
+    /// A typed password or, when nothing was typed, a generated one (under the passwordrules of
+    /// the service)
+    fn new_password(
+        &mut self,
+        getch: &Getch,
+        service: &str,
+    ) -> Result<Option<Result<String, String>>, Locked> {
+        let Some(password) = self.prompt(
+            getch,
+            &format!("Password of {service} (nothing to generate one): "),
+            true,
+        )?
+        else {
+            return Ok(None);
+        };
+        if !password.is_empty() {
+            return Ok(Some(Ok(password)));
+        }
+        let generator = match self.password_array.get_password_rules(service) {
+            Ok(Some(rules)) => Generator::Rules(rules),
+            _ => Generator::Random(PasswordPolicy::default()),
+        };
+        self.reveal(service.to_string());
+        Ok(Some(generate_password(&generator)))
+    }
+
+    /// What's wrong with a new password, if anything
+    fn password_warning(&self, password: &str) -> Option<String> {
+        if let Some(database) = self.breach_database
+            && let Ok(count) = database.count(password)
+            && count > 0
+        {
+            return Some(format!("was found in {count} breaches"));
+        }
+        let strength = estimate(password);
+        (!strength.is_strong()).then(|| format!("is {}", strength.score_name()))
+    }
+
+    /// The status after setting the password of `service`
+    fn password_status(&self, service: &str, password: &str, done: &str) -> String {
+        match self.password_warning(password) {
+            Some(warning) => {
+                format!("{YELLOW}Warning:{RESET} the password of {service} {warning}")
+            }
+            None => format!("{GREEN}{done} {service}{RESET}"),
+        }
+    }
+
+    fn add(&mut self, getch: &Getch) -> Result<(), Locked> {
+        let Some(service) = self.prompt(getch, "Service: ", false)? else {
+            return Ok(());
+        };
+        let service = service.trim().to_string();
+        if service.is_empty() {
+            return Ok(());
+        }
+        if self.password_array.get_services().contains(&service) {
+            self.status = format!("{RED}{service} already exists{RESET}");
+            return Ok(());
+        }
+        let password = match self.new_password(getch, &service)? {
+            Some(Ok(password)) => password,
+            Some(Err(error)) => {
+                self.status = format!("{RED}{error}{RESET}");
+                return Ok(());
+            }
+            None => return Ok(()),
+        };
+        let status = self.password_status(&service, &password, "Added");
+        if let Err(error) = self
+            .password_array
+            .add_password(service.clone(), SecretString::from(password))
+        {
+            self.status = format!("{RED}{error}{RESET}");
+            return Ok(());
+        }
+        *self.modified = true;
+        self.status = status;
+        self.search.clear();
+        self.filter();
+        self.select(&service);
+        Ok(())
+    }
+
+    fn edit(&mut self, getch: &Getch) -> Result<(), Locked> {
+        let Some(service) = self.selected_service() else {
+            return Ok(());
+        };
+        match self.new_password(getch, &service)? {
+            Some(Ok(password)) => self.set_password(&service, password, "Changed the password of"),
+            Some(Err(error)) => self.status = format!("{RED}{error}{RESET}"),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn rename(&mut self, getch: &Getch) -> Result<(), Locked> {
+        let Some(service) = self.selected_service() else {
+            return Ok(());
+        };
+        let Some(new_name) = self.prompt(getch, &format!("Rename {service} to: "), false)? else {
+            return Ok(());
+        };
+        let new_name = new_name.trim().to_string();
+        if new_name.is_empty() || new_name == service {
+            return Ok(());
+        }
+        if let Err(error) = self
+            .password_array
+            .rename_service(&service, new_name.clone())
+        {
+            self.status = format!("{RED}{error}{RESET}");
+            return Ok(());
+        }
+        *self.modified = true;
+        self.status = format!("{GREEN}Renamed {service} to {new_name}{RESET}");
+        self.search.clear();
+        self.filter();
+        self.select(&new_name);
+        Ok(())
+    }
+
+    /// Replaces the password of the selected service with a generated one
+    fn generate(&mut self, getch: &Getch) -> Result<(), Locked> {
+        let Some(service) = self.selected_service() else {
+            return Ok(());
+        };
+        if !self.confirm(
+            getch,
+            &format!("Replace the password of {service} with a generated one? (y/n) "),
+        )? {
+            return Ok(());
+        }
+        let generator = match self.password_array.get_password_rules(&service) {
+            Ok(Some(rules)) => Generator::Rules(rules),
+            _ => Generator::Random(PasswordPolicy::default()),
+        };
+        match generate_password(&generator) {
+            Ok(password) => {
+                self.reveal(service.clone());
+                self.set_password(&service, password, "Generated a new password for");
+            }
+            Err(error) => self.status = format!("{RED}{error}{RESET}"),
+        }
+        Ok(())
+    }
+
+    fn set_password(&mut self, service: &str, password: String, done: &str) {
+        let status = self.password_status(service, &password, done);
+        if let Err(error) = self
+            .password_array
+            .edit_password(service.to_string(), SecretString::from(password))
+        {
+            self.status = format!("{RED}{error}{RESET}");
+            return;
+        }
+        *self.modified = true;
+        self.status = status;
+    }
+
+    fn delete(&mut self, getch: &Getch) -> Result<(), Locked> {
+        let Some(service) = self.selected_service() else {
+            return Ok(());
+        };
+        if !self.confirm(getch, &format!("Delete {service}? (y/n) "))? {
+            return Ok(());
+        }
+        if let Err(error) = self.password_array.remove_password(service.clone()) {
+            self.status = format!("{RED}{error}{RESET}");
+            return Ok(());
+        }
+        *self.modified = true;
+        self.status = format!("{GREEN}Deleted {service}{RESET}");
+        self.filter();
+        Ok(())
+    }
+
+    fn copy(&mut self) {
+        let Some(service) = self.selected_service() else {
+            return;
+        };
+        let result = self
+            .password_array
+            .get_password(&service)
+            .and_then(|password| copy_to_clipboard(password.expose_secret(), self.clip_timeout));
+        self.status = match result {
+            Ok(()) if self.clip_timeout == 0 => {
+                format!("{GREEN}Copied the password of {service}{RESET}")
+            }
+            Ok(()) => format!(
+                "{GREEN}Copied the password of {service}, it's cleared in {} seconds{RESET}",
+                self.clip_timeout
+            ),
+            Err(error) => format!("{RED}{error}{RESET}"),
+        };
+    }
+
+    /// Undoes the last change (or redoes the last undone one)
+    fn undo(&mut self, redo: bool) {
+        let (result, done, nothing) = if redo {
+            (
+                self.password_array.redo(),
+                "Redid",
+                "There's nothing to redo",
+            )
+        } else {
+            (
+                self.password_array.undo(),
+                "Undid",
+                "There's nothing to undo",
+            )
+        };
+        self.status = match result {
+            Some(description) => format!("{GREEN}{done}: {description}{RESET}"),
+            None => format!("{RED}{nothing}{RESET}"),
+        };
+        *self.modified = self.password_array.has_unsaved_changes();
+        self.filter();
+    }
+
+    fn save(&mut self) {
+        self.draw(Some("Saving..."));
+        self.status = match self.password_array.save(false) {
+            Ok(()) => {
+                *self.modified = false;
+                format!("{GREEN}Saved{RESET}")
+            }
+            Err(error) => format!("{RED}{error}{RESET}"),
+        };
+    }
+
+    /// Draws the whole screen, `prompt` replaces the status (or the keys)
+    fn draw(&mut self, prompt: Option<&str>) {
+        let size = terminal_size();
+        let (columns, rows) = (size.0.max(MIN_SIZE.0), size.1.max(MIN_SIZE.1));
+        let list_width = (columns / 3).clamp(12, 40);
+        let details_width = columns - list_width - 3;
+        let list_rows = rows - 3;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+        let details = match self.selected_service() {
+            Some(service) => details(self.password_array, &service, self.is_revealed())
+                .unwrap_or_else(|error| vec![format!("{RED}{error}{RESET}")]),
+            None if self.search.is_empty() => vec![String::from("There are no passwords")],
+            None => vec![format!("Nothing matches {}", self.search)],
+        };
+        let mut frame = String::new();
+        if size != self.size {
+            frame.push_str(CLEAR);
+            self.size = size;
+        }
+        let title = if self.search.is_empty() {
+            String::from("Services")
+        } else {
+            format!("Services matching {}", self.search)
+        };
+        frame += &format!(
+            "{}{BOLD}{}{RESET} │ {BOLD}{}{RESET}",
+            Csi::MoveTo(1, 1),
+            fit(&title, list_width),
+            fit("Details", details_width)
+        );
+        for row in 0..list_rows {
+            let index = self.scroll + row;
+            let service = match self.services.get(index) {
+                Some(service) if index == self.selected => {
+                    format!(
+                        "{REVERSE}{}{RESET}",
+                        fit(&format!(" {service}"), list_width)
+                    )
+                }
+                Some(service) => fit(&format!(" {service}"), list_width),
+                None => fit("", list_width),
+            };
+            let detail = details.get(row).map(String::as_str).unwrap_or_default();
+            frame += &format!(
+                "{}{service} │ {}",
+                Csi::MoveTo(row + 2, 1),
+                fit(detail, details_width)
+            );
+        }
+        let message = match prompt {
+            Some(prompt) => format!("{prompt}{REVERSE} {RESET}"),
+            None if !self.status.is_empty() => self.status.clone(),
+            None => format!("{BRIGHT_BLACK}{KEYS}{RESET}"),
+        };
+        let directory = self.password_array.get_directory();
+        let vault = Path::new(directory)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| directory.to_string());
+        let unsaved = if *self.modified {
+            " │ [+] unsaved changes"
+        } else {
+            ""
+        };
+        let status_bar = format!(" {vault} │ {} entries{unsaved}", self.services_total());
+        // the last column of the last row is left empty so the terminal doesn't scroll
+        frame += &format!(
+            "{}{}{}{REVERSE}{}{RESET}",
+            Csi::MoveTo(rows - 1, 1),
+            fit(&message, columns),
+            Csi::MoveTo(rows, 1),
+            fit(&status_bar, columns - 1)
+        );
+        print!("{frame}");
+        let _ = io::stdout().flush();
+    }
+
+    fn services_total(&self) -> usize {
+        self.password_array.get_services().len()
+    }
+}
+
+/// Cuts `text` off or pads it with spaces so it takes up `width` columns (escape sequences don't
+/// count)
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut shown = 0;
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character == '\x1b' {
+            fitted.push(character);
+            for character in characters.by_ref() {
+                fitted.push(character);
+                if character.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if shown == width {
+            fitted.push_str(RESET);
+            return fitted;
+        }
+        fitted.push(character);
+        shown += 1;
+    }
+    fitted + &" ".repeat(width - shown)
+}
+
+#[cfg(unix)]
+nix::ioctl_read_bad!(window_size, nix::libc::TIOCGWINSZ, nix::libc::winsize);
+
+/// The columns and rows of the terminal
+#[cfg(unix)]
+fn terminal_size() -> (usize, usize) {
+    let mut size = nix::libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes a winsize to the pointer, which points to one
+    match unsafe { window_size(nix::libc::STDOUT_FILENO, &mut size) } {
+        Ok(_) if size.ws_col > 0 && size.ws_row > 0 => (size.ws_col as usize, size.ws_row as usize),
+        _ => DEFAULT_SIZE,
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> (usize, usize) {
+    DEFAULT_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault(services: &[&str]) -> PasswordArray {
+        let mut password_array =
+            PasswordArray::new(SecretString::from("master"), String::from("tui"));
+        for service in services {
+            password_array
+                .add_password(service.to_string(), SecretString::from("password"))
+                .unwrap();
+        }
+        password_array
+    }
+
+    #[test]
+    fn fit_pads_and_cuts() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abc", 3), "abc");
+        assert_eq!(fit("abcdef", 3), format!("abc{RESET}"));
+        assert_eq!(fit("", 2), "  ");
+        // escape sequences don't take up columns
+        assert_eq!(
+            fit(&format!("{BOLD}ab{RESET}"), 3),
+            format!("{BOLD}ab{RESET} ")
+        );
+        assert_eq!(fit(&format!("{BOLD}abc"), 2), format!("{BOLD}ab{RESET}"));
+    }
+
+    #[test]
+    fn filter_keeps_the_selection() {
+        let mut password_array = vault(&["github", "gitlab", "google"]);
+        let mut modified = false;
+        let mut tui = Tui::new(&mut password_array, &mut modified, 0, None);
+        tui.filter();
+        assert_eq!(tui.services, ["github", "gitlab", "google"]);
+        tui.select("gitlab");
+
+        tui.search = String::from("gtl");
+        tui.filter();
+        assert_eq!(tui.services, ["gitlab"]);
+        assert_eq!(tui.selected_service().as_deref(), Some("gitlab"));
+
+        tui.search = String::from("git");
+        tui.filter();
+        assert_eq!(tui.services.len(), 2);
+        assert_eq!(tui.selected_service().as_deref(), Some("gitlab"));
+
+        // the selection goes back to the top when the selected service doesn't match
+        tui.search = String::from("goo");
+        tui.filter();
+        assert_eq!(tui.services, ["google"]);
+        assert_eq!(tui.selected, 0);
+
+        tui.search = String::from("nothing");
+        tui.filter();
+        assert!(tui.services.is_empty());
+        assert_eq!(tui.selected_service(), None);
+    }
+
+    #[test]
+    fn move_by_stays_in_the_list() {
+        let mut password_array = vault(&["a", "b", "c"]);
+        let mut modified = false;
+        let mut tui = Tui::new(&mut password_array, &mut modified, 0, None);
+        tui.filter();
+        tui.move_by(1);
+        assert_eq!(tui.selected, 1);
+        tui.move_by(10);
+        assert_eq!(tui.selected, 2);
+        tui.move_by(-10);
+        assert_eq!(tui.selected, 0);
+
+        tui.reveal(String::from("a"));
+        assert!(tui.is_revealed());
+        tui.move_by(1);
+        assert!(!tui.is_revealed());
+
+        tui.search = String::from("nothing");
+        tui.filter();
+        tui.move_by(1);
+        assert_eq!(tui.selected, 0);
+    }
+}
@@ -1,20 +1,45 @@
 use crate::{
-    ansi::{CLEAR, Csi, EL, colors::AnsiRGB, constants::*},
+    ansi::{CLEAR, Csi, EL, Osc, colors::AnsiRGB, constants::*, write_to_terminal},
+    breach::BreachDatabase,
     cryptography::check_hash,
-    storage::{get_master_password, initialize_directory, verify_directory},
+    exitcode, fuzzy,
+    generator::{
+        Capitalization, CharClass, Generator, PassphrasePolicy, PasswordPolicy, PatternPolicy,
+        PronounceablePolicy, Wordlist,
+    },
+    passwordrules::PasswordRules,
+    passwordsource::PasswordSource,
+    rotation,
+    storage::{
+        PasswordArray, VaultSettings, get_master_password, initialize_directory, unix_time,
+        verify_directory,
+    },
+    strength::estimate,
 };
 use getch_rs::{Getch, Key};
-use rand::{Rng, SeedableRng, rngs::StdRng};
 use secrecy::{ExposeSecret, SecretString};
 use std::{
-    collections::HashSet,
     fmt::Display,
     fs,
-    io::{self, Write},
+    io::{self, IsTerminal, Write},
     mem::take,
+    panic,
+    process::{self, Stdio},
+    sync::{
+        Once, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 use std::{path::Path, process::exit};
 
+/// Shown instead of a password (always as long so it doesn't give away the length)
+pub const MASK: &str = "********";
+/// Seconds until a copied password is cleared from the clipboard
+pub const DEFAULT_CLIP_TIMEOUT: u64 = 30;
+/// Minutes without a key until the interactive session is locked
+pub const DEFAULT_LOCK_TIMEOUT: u64 = 5;
 const V: &str = "✔";
 const W: &str = "⚠︎";
 const HELP_MESSAGE: &str = "There are a total of 7 commands (which have alaises):\n\nchoose (no other alias): Chooses a directory. Only accepts directories with the correct files\ncd (no other alias): Changes current working directory\nls (no other alias): Lists the contents of the current working directory\nexit (q, quit, ex): Exits the program\nclear (c, cls): clears the screen\nnew (init, new_session, make): clears the screen and prompts the user for the new directories name and the master password to store the hash in the master_password file\nhelp (h, ?): Shows this help\n\nUsage:\n\nCommands with no arguments: ls, exit, clear, help, new\n\ncd: cd {dirname}\nchoose: choose {dirname}";
@@ -37,14 +62,6 @@ pub const YESES: [&str; 15] = [
     "se",
 ];
 
-const CHARS: [&str; 94] = [
-    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s",
-    "t", "u", "v", "w", "x", "y", "z", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L",
-    "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z", "0", "1", "2", "3", "4",
-    "5", "6", "7", "8", "9", "!", "\"", "#", "$", "%", "&", "'", "(", ")", "*", "+", ",", "-", ".",
-    "/", ":", ";", "<", "=", ">", "?", "@", "[", "\\", "]", "^", "_", "`", "{", "|", "}", "~",
-];
-
 pub const ALL_FLAGS: [InputFlags; 4] = [
     InputFlags::HighlightInput,
     InputFlags::IsBlacklist,
@@ -173,29 +190,24 @@ impl Menu {
         }
     }
 
-    pub fn interact(&mut self) -> usize {
+    pub fn interact(&mut self) -> Result<usize, Locked> {
         println!("{}", Csi::Hide);
         println!("{CLEAR}");
         let getch = Getch::new();
         println!("{}", self.prompt);
         self.print_items();
         loop {
-            let chr = getch.getch();
+            let chr = read_key(&getch)?;
             match chr {
                 Ok(Key::Char('\r')) => {
                     println!("{}", Csi::Show);
                     println!("{CLEAR}");
-                    return self.selection.index;
-                }
-                Ok(Key::Char('j')) | Ok(Key::Down) | Ok(Key::Char('l')) => {
-                    self.selection.next();
+                    return Ok(self.selection.index);
                 }
-                Ok(Key::Up) | Ok(Key::Char('k')) | Ok(Key::Backspace) | Ok(Key::Delete)
-                | Ok(Key::Char('h')) => {
-                    self.selection.prev();
+                Ok(Key::Ctrl('c')) => quit(1),
+                Ok(key) => {
+                    self.selection.navigate(&key);
                 }
-                Ok(Key::Ctrl('c')) => exit(1),
-                Ok(_key) => {}
                 Err(e) => eprintln!("{e}"),
             }
             println!("{CLEAR}");
@@ -219,6 +231,332 @@ impl Menu {
     }
 }
 
+/// A [Menu] where more than one option can be chosen (space toggles, a toggles everything)
+pub struct MultiSelect {
+    selection: VecIndex<String>,
+    checked: Vec<bool>,
+    prompt: String,
+    icon: String,
+}
+
+impl MultiSelect {
+    /// `checked` is which options start out chosen
+    pub fn new(menu_config: MenuConfig, options: Vec<String>, checked: Vec<bool>) -> Self {
+        let mut checked = checked;
+        checked.resize(options.len(), false);
+        Self {
+            selection: VecIndex::new(options),
+            checked,
+            prompt: menu_config.prompt,
+            icon: menu_config.icon,
+        }
+    }
+
+    /// Returns the indexes of the chosen options
+    pub fn interact(&mut self) -> Result<Vec<usize>, Locked> {
+        println!("{}", Csi::Hide);
+        println!("{CLEAR}");
+        let getch = Getch::new();
+        self.print_items();
+        loop {
+            let chr = read_key(&getch)?;
+            match chr {
+                Ok(Key::Char('\r')) => {
+                    println!("{}", Csi::Show);
+                    println!("{CLEAR}");
+                    return Ok((0..self.checked.len())
+                        .filter(|index| self.checked[*index])
+                        .collect());
+                }
+                Ok(Key::Char(' ')) if !self.checked.is_empty() => {
+                    let index = self.selection.index;
+                    self.checked[index] = !self.checked[index];
+                }
+                Ok(Key::Char('a')) => {
+                    let all = self.checked.iter().all(|checked| *checked);
+                    self.checked.iter_mut().for_each(|checked| *checked = !all);
+                }
+                Ok(Key::Ctrl('c')) => quit(1),
+                Ok(key) => {
+                    self.selection.navigate(&key);
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+            println!("{CLEAR}");
+            self.print_items();
+        }
+    }
+    fn print_items(&self) {
+        println!("{}", self.prompt);
+        println!("(space: choose, a: choose all, enter: done)");
+        let space = " ".repeat(self.icon.len() + 1);
+        for (index, option) in self.selection.vector.iter().enumerate() {
+            let pointer = if index == self.selection.index {
+                format!("{} ", self.icon)
+            } else {
+                space.clone()
+            };
+            let checkbox = if self.checked[index] { "[x]" } else { "[ ]" };
+            println!("{pointer}{checkbox} {option}");
+        }
+    }
+}
+
+/// How long a revealed password is shown (in [PasswordList] and the TUI)
+pub const REVEAL_SECONDS: u64 = 5;
+
+/// A list of services with masked passwords where one password at a time can be revealed (for
+/// [REVEAL_SECONDS]) or copied
+pub struct PasswordList {
+    selection: VecIndex<(String, SecretString)>,
+    prompt: String,
+    icon: String,
+    clip_timeout: u64,
+}
+
+impl PasswordList {
+    /// `entries` are services with their passwords, copied passwords are cleared from the
+    /// clipboard after `clip_timeout` seconds
+    pub fn new(
+        menu_config: MenuConfig,
+        entries: Vec<(String, SecretString)>,
+        clip_timeout: u64,
+    ) -> Self {
+        Self {
+            selection: VecIndex::new(entries),
+            prompt: menu_config.prompt,
+            icon: menu_config.icon,
+            clip_timeout,
+        }
+    }
+
+    pub fn interact(&mut self) -> Result<(), Locked> {
+        if self.selection.vector.is_empty() {
+            println!("There are no passwords");
+            return pause();
+        }
+        println!("{}", Csi::Hide);
+        let getch = Getch::new();
+        let mut revealed_until: Option<Instant> = None;
+        let mut status = String::new();
+        loop {
+            println!("{CLEAR}");
+            self.print_items(revealed_until.is_some(), &status);
+            let timeout =
+                revealed_until.map(|until| until.saturating_duration_since(Instant::now()));
+            let chr = match wait_for_key(&getch, timeout)? {
+                Some(chr) => chr,
+                None => {
+                    revealed_until = None;
+                    continue;
+                }
+            };
+            match chr {
+                Ok(Key::Char('q')) | Ok(Key::Esc) | Ok(Key::Char('\r')) => break,
+                Ok(Key::Ctrl('c')) => quit(1),
+                _ => {}
+            }
+            status.clear();
+            match chr {
+                Ok(Key::Char('r')) | Ok(Key::Char(' ')) => {
+                    revealed_until = Some(Instant::now() + Duration::from_secs(REVEAL_SECONDS));
+                }
+                Ok(Key::Char('c')) => {
+                    let (service, password) = &self.selection.vector[self.selection.index];
+                    status = match copy_to_clipboard(password.expose_secret(), self.clip_timeout) {
+                        Ok(()) if self.clip_timeout == 0 => {
+                            format!("{GREEN}Copied the password of {service}{RESET}")
+                        }
+                        Ok(()) => format!(
+                            "{GREEN}Copied the password of {service}, it's cleared in {} seconds{RESET}",
+                            self.clip_timeout
+                        ),
+                        Err(error) => format!("{RED}{error}{RESET}"),
+                    };
+                }
+                // moving hides the revealed password
+                Ok(key) if self.selection.navigate(&key) => revealed_until = None,
+                Err(e) => eprintln!("{e}"),
+                _ => {}
+            }
+        }
+        drop(getch);
+        println!("{}", Csi::Show);
+        println!("{CLEAR}");
+        Ok(())
+    }
+    fn print_items(&self, reveal: bool, status: &str) {
+        println!("{}", self.prompt);
+        println!("(r: reveal for {REVEAL_SECONDS}s, c: copy, q: back)");
+        let width = self
+            .selection
+            .vector
+            .iter()
+            .map(|(service, _)| service.chars().count())
+            .max()
+            .unwrap_or_default();
+        let space = " ".repeat(self.icon.len() + 1);
+        for (index, (service, password)) in self.selection.vector.iter().enumerate() {
+            let selected = index == self.selection.index;
+            let pointer = if selected {
+                format!("{} ", self.icon)
+            } else {
+                space.clone()
+            };
+            let password = if selected && reveal {
+                password.expose_secret()
+            } else {
+                MASK
+            };
+            println!("{pointer}{service:<width$}  {password}");
+        }
+        if !status.is_empty() {
+            println!("\n{status}");
+        }
+    }
+}
+
+/// How many matches [FuzzyPicker] shows at once
+const PICKER_ROWS: usize = 10;
+
+/// Searches through names as you type (the characters have to appear in order, see
+/// [fuzzy::score]), the best and most recently changed matches come first and the arrow keys
+/// choose between them
+///
+/// It's drawn on stderr so the chosen name can be printed on stdout
+pub struct FuzzyPicker {
+    /// Names with when they were last changed (unix time)
+    entries: Vec<(String, Option<u64>)>,
+    prompt: String,
+    icon: String,
+}
+
+impl FuzzyPicker {
+    pub fn new(menu_config: MenuConfig, entries: Vec<(String, Option<u64>)>) -> Self {
+        Self {
+            entries,
+            prompt: menu_config.prompt,
+            icon: menu_config.icon,
+        }
+    }
+
+    /// Starts searching for `query`, returns the chosen name or None if escape was pressed
+    pub fn interact(&self, query: String) -> Result<Option<String>, Locked> {
+        let mut query = query;
+        let mut selected = 0;
+        let mut chosen = None;
+        eprint!("{}", Csi::Hide);
+        let getch = Getch::new();
+        loop {
+            let matches = self.matches(&query);
+            selected = selected.min(matches.len().saturating_sub(1));
+            self.print_items(&query, &matches, selected);
+            match read_key(&getch)? {
+                Ok(Key::Char('\r')) => {
+                    if let Some((index, _)) = matches.get(selected) {
+                        chosen = Some(self.entries[*index].0.clone());
+                        break;
+                    }
+                }
+                Ok(Key::Esc) => break,
+                Ok(Key::Ctrl('c')) => {
+                    drop(getch);
+                    eprint!("{}", Csi::Show);
+                    quit(1)
+                }
+                Ok(Key::Up) | Ok(Key::Ctrl('p')) | Ok(Key::BackTab) => {
+                    selected = selected
+                        .checked_sub(1)
+                        .unwrap_or(matches.len().saturating_sub(1));
+                }
+                Ok(Key::Down) | Ok(Key::Ctrl('n')) | Ok(Key::Char('\t')) => {
+                    selected = if selected + 1 >= matches.len() {
+                        0
+                    } else {
+                        selected + 1
+                    };
+                }
+                Ok(Key::Backspace) | Ok(Key::Delete) => {
+                    query.pop();
+                    selected = 0;
+                }
+                Ok(Key::Ctrl('u')) => {
+                    query.clear();
+                    selected = 0;
+                }
+                Ok(Key::Char(character)) if !character.is_control() => {
+                    query.push(character);
+                    selected = 0;
+                }
+                Err(e) => eprintln!("{e}"),
+                _ => {}
+            }
+        }
+        drop(getch);
+        eprint!("{}{CLEAR}", Csi::Show);
+        Ok(chosen)
+    }
+
+    /// The indexes of the entries matching `query` (best first) with the positions that matched
+    fn matches(&self, query: &str) -> Vec<(usize, Vec<usize>)> {
+        let now = unix_time();
+        let mut matches: Vec<(i64, usize, Vec<usize>)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (name, changed))| {
+                let (score, positions) = fuzzy::score(query, name)?;
+                Some((
+                    score + fuzzy::recency_bonus(*changed, now),
+                    index,
+                    positions,
+                ))
+            })
+            .collect();
+        matches.sort_by(|(a_score, a, _), (b_score, b, _)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| self.entries[*a].0.cmp(&self.entries[*b].0))
+        });
+        matches
+            .into_iter()
+            .map(|(_, index, positions)| (index, positions))
+            .collect()
+    }
+
+    fn print_items(&self, query: &str, matches: &[(usize, Vec<usize>)], selected: usize) {
+        let mut output = format!("{CLEAR}{}{query}\n", self.prompt);
+        output += &format!(
+            "{}/{} (up/down: choose, enter: pick, esc: cancel)\n",
+            matches.len(),
+            self.entries.len()
+        );
+        let space = " ".repeat(self.icon.len() + 1);
+        let first = (selected + 1).saturating_sub(PICKER_ROWS);
+        for (row, (index, positions)) in matches.iter().enumerate().skip(first).take(PICKER_ROWS) {
+            let pointer = if row == selected {
+                format!("{} ", self.icon)
+            } else {
+                space.clone()
+            };
+            let name: String = self.entries[*index]
+                .0
+                .chars()
+                .enumerate()
+                .map(|(position, character)| {
+                    if positions.contains(&position) {
+                        format!("{BOLD}{GREEN}{character}{RESET}")
+                    } else {
+                        character.to_string()
+                    }
+                })
+                .collect();
+            output += &format!("{pointer}{name}\n");
+        }
+        eprint!("{output}");
+    }
+}
+
 struct VecIndex<T> {
     vector: Vec<T>,
     index: usize,
@@ -229,6 +567,9 @@ impl<T> VecIndex<T> {
         Self { vector, index: 0 }
     }
     fn next(&mut self) {
+        if self.vector.is_empty() {
+            return;
+        }
         if self.index + 1 >= self.vector.len() {
             self.index = 0;
         } else {
@@ -236,12 +577,24 @@ impl<T> VecIndex<T> {
         }
     }
     fn prev(&mut self) {
+        if self.vector.is_empty() {
+            return;
+        }
         if self.index == 0 {
             self.index = self.vector.len() - 1
         } else {
             self.index -= 1
         }
     }
+    /// Moves the selection if `key` is one of the keys menus move with, returns if it was
+    fn navigate(&mut self, key: &Key) -> bool {
+        match key {
+            Key::Char('j') | Key::Down | Key::Char('l') => self.next(),
+            Key::Up | Key::Char('k') | Key::Backspace | Key::Delete | Key::Char('h') => self.prev(),
+            _ => return false,
+        }
+        true
+    }
 }
 
 // this is ugly
@@ -328,43 +681,54 @@ fn directory_selector_prompt(format_string: &str) -> String {
     res
 }
 
-fn evaluate_password(password: &str) {
-    let (length, upper, digits, lower, unique) = (
-        password.chars().count(),
-        password.chars().filter(|s| s.is_uppercase()).count(),
-        password.chars().filter(|s| s.is_numeric()).count(),
-        password.chars().filter(|s| s.is_lowercase()).count(),
-        password.chars().collect::<HashSet<char>>().len(),
+fn evaluate_password(password: &str, breach_database: Option<&BreachDatabase>) {
+    let strength = estimate(password);
+    let color = match strength.score {
+        0 | 1 => RED,
+        2 => YELLOW,
+        3 => BLUE,
+        _ => GREEN,
+    };
+    let bar = "■".repeat(strength.score as usize + 1) + &"□".repeat(4 - strength.score as usize);
+    println!(
+        "{color}{bar}{RESET} {BOLD}{}{RESET} ({:.1} bits)",
+        strength.score_name(),
+        strength.entropy()
     );
-    let special = length - (upper + digits + lower);
-    let results: [bool; 5] = [length < 15, upper < 4, unique < 7, digits < 4, special < 4];
-    let messages = [
-        "The length of the password should be 15 characters long",
-        "The password should have atleast 4 capital letters",
-        "The password should have atleast 7 unique characters",
-        "The password should have atleast 4 digits",
-        "The password should have atleast 4 special characters",
-    ];
-    for (message, suggestion) in messages.iter().zip(results) {
-        if suggestion {
-            println!("{W} {YELLOW}{message}{RESET}");
-            continue;
+    for (attack, time) in strength.crack_times() {
+        println!("  {attack}: {time}");
+    }
+    if let Some(warning) = &strength.warning {
+        println!("{W} {YELLOW}{warning}{RESET}");
+    }
+    for suggestion in &strength.suggestions {
+        println!("{CYAN}• {suggestion}{RESET}");
+    }
+    if let Some(breach_database) = breach_database
+        && !password.is_empty()
+    {
+        match breach_database.count(password) {
+            Ok(0) => println!("{V}{GREEN} Not found in the breach database{RESET}"),
+            Ok(count) => println!("{W} {RED}Found {count} times in breaches{RESET}"),
+            Err(error) => println!("{W} {YELLOW}{error}{RESET}"),
         }
-        println!("{V}{GREEN} {message}{RESET}");
     }
 }
 
-/// Makes a password prompt with password suggestions
-pub fn new_password_input(prompt: impl Display) -> secrecy::SecretString {
+/// Makes a password prompt with password suggestions (and a breach check if there's a database)
+pub fn new_password_input(
+    prompt: impl Display,
+    breach_database: Option<&BreachDatabase>,
+) -> Result<SecretString, Locked> {
     let getch = Getch::new();
     let mut password = String::new();
     println!("{CLEAR}");
     println!("{prompt}");
-    evaluate_password(&password);
+    evaluate_password(&password, breach_database);
     loop {
-        let chr = getch.getch();
+        let chr = read_key(&getch)?;
         match chr {
-            Ok(Key::Char('\r')) => return secrecy::SecretString::from(password),
+            Ok(Key::Char('\r')) => return Ok(SecretString::from(password)),
             Ok(Key::Char(c)) => {
                 password.push(c);
             }
@@ -374,26 +738,37 @@ pub fn new_password_input(prompt: impl Display) -> secrecy::SecretString {
             Ok(Key::Delete) => {
                 password.pop();
             }
-            Ok(Key::Ctrl('c')) => exit(1),
+            Ok(Key::Ctrl('c')) => quit(1),
             Ok(_key) => {}
             Err(e) => eprintln!("{e}"),
         }
         println!("{CLEAR}");
         println!("{prompt}{}", "*".repeat(password.len()));
-        evaluate_password(&password);
+        evaluate_password(&password, breach_database);
     }
 }
 
-/// Makes a password prompt with no password suggestions
+/// Makes a password prompt with no password suggestions (on stderr so stdout only has what
+/// commands print), when stdin is piped the password is read from the terminal itself
 pub fn password_input(prompt: impl Display) -> SecretString {
+    #[cfg(unix)]
+    if !io::stdin().is_terminal() {
+        return tty_password_input(prompt).unwrap_or_else(|error| {
+            eprintln!("{RED}Error: {error}{RESET}");
+            quit(1)
+        });
+    }
     let getch = Getch::new();
     let mut password = String::new();
-    println!("{CLEAR}");
-    print!("{prompt}");
-    let mut buf = io::stdout();
+    eprintln!("{CLEAR}");
+    eprint!("{prompt}");
+    let mut buf = io::stderr();
     let _ = buf.flush();
     loop {
-        let chr = getch.getch();
+        // it's only used for the master password, there's nothing to lock while it's typed in
+        let Ok(chr) = read_key(&getch) else {
+            continue;
+        };
         match chr {
             Ok(Key::Char('\r')) => return SecretString::from(password),
             Ok(Key::Char(c)) => {
@@ -405,16 +780,77 @@ pub fn password_input(prompt: impl Display) -> SecretString {
             Ok(Key::Delete) => {
                 password.pop();
             }
-            Ok(Key::Ctrl('c')) => exit(1),
+            Ok(Key::Ctrl('c')) => quit(1),
             Ok(_key) => {}
             Err(e) => eprintln!("{e}"),
         }
-        println!("{CLEAR}");
-        print!("{prompt}{}", "*".repeat(password.len()));
+        eprintln!("{CLEAR}");
+        eprint!("{prompt}{}", "*".repeat(password.len()));
         let _ = buf.flush();
     }
 }
 
+/// [password_input] that reads from (and writes to) `/dev/tty` so stdin can be piped
+#[cfg(unix)]
+fn tty_password_input(prompt: impl Display) -> Result<SecretString, String> {
+    use nix::sys::termios::{self, LocalFlags, SetArg};
+    use std::{io::Read, os::fd::AsRawFd};
+    let mut tty = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|err| format!("Stdin is piped and the terminal can't be opened: {err}"))?;
+    let fd = tty.as_raw_fd();
+    let error = |err: nix::Error| format!("Error with the terminal: {err}");
+    let original = termios::tcgetattr(fd).map_err(error)?;
+    let mut raw = original.clone();
+    raw.local_flags
+        .remove(LocalFlags::ECHO | LocalFlags::ICANON | LocalFlags::ISIG);
+    termios::tcsetattr(fd, SetArg::TCSADRAIN, &raw).map_err(error)?;
+    let mut password: Vec<u8> = vec![];
+    let mut byte = [0u8];
+    let _ = write!(tty, "{CLEAR}\n{prompt}");
+    loop {
+        let _ = tty.flush();
+        if tty.read(&mut byte).unwrap_or(0) == 0 {
+            break;
+        }
+        match byte[0] {
+            b'\r' | b'\n' => break,
+            // backspace removes a whole character
+            0x7f | 0x08 => {
+                while let Some(last) = password.pop() {
+                    if last & 0b1100_0000 != 0b1000_0000 {
+                        break;
+                    }
+                }
+            }
+            // Ctrl-C
+            0x03 => {
+                let _ = termios::tcsetattr(fd, SetArg::TCSADRAIN, &original);
+                quit(1)
+            }
+            // escape sequences (arrow keys and such) are skipped
+            0x1b => {
+                if tty.read(&mut byte).unwrap_or(0) == 1 && byte[0] == b'[' {
+                    while tty.read(&mut byte).unwrap_or(0) == 1 && !(0x40..=0x7e).contains(&byte[0])
+                    {
+                    }
+                }
+            }
+            control if control < 0x20 => {}
+            other => password.push(other),
+        }
+        let stars = "*".repeat(String::from_utf8_lossy(&password).chars().count());
+        let _ = write!(tty, "{CLEAR}\n{prompt}{stars}");
+    }
+    let _ = termios::tcsetattr(fd, SetArg::TCSADRAIN, &original);
+    let _ = writeln!(tty);
+    String::from_utf8(password)
+        .map(SecretString::from)
+        .map_err(|_| String::from("The password isn't valid UTF-8"))
+}
+
 fn list_directory(path: &str) {
     for p in fs::read_dir(path).unwrap() {
         let path = p.unwrap().path();
@@ -427,7 +863,7 @@ fn list_directory(path: &str) {
     }
 }
 
-fn parse(string: &String, commands: &[String], parse_invalid: bool) -> Vec<Token> {
+fn parse(string: &str, commands: &[String], parse_invalid: bool) -> Vec<Token> {
     if string.is_empty() {
         return Vec::new();
     }
@@ -477,7 +913,7 @@ pub fn input(
     default: String,
     commands: &[String],
     flags: &[InputFlags],
-) -> String {
+) -> Result<String, Locked> {
     let (deny_empty_input, is_blacklist, highlight_text, deny_blacklist) = (
         flags.contains(&InputFlags::DenyEmptyInput),
         flags.contains(&InputFlags::IsBlacklist),
@@ -490,14 +926,14 @@ pub fn input(
     print!("{prompt}");
     let _ = stdout.flush();
     loop {
-        let chr = getch.getch();
+        let chr = read_key(&getch)?;
         match chr {
             Ok(Key::Char('\r')) => {
                 if deny_empty_input && buffer.is_empty() {
                     continue;
                 }
                 if buffer.is_empty() && !default.is_empty() {
-                    return default;
+                    return Ok(default);
                 }
                 if is_blacklist
                     && deny_blacklist
@@ -508,7 +944,7 @@ pub fn input(
                     continue;
                 }
                 println!();
-                return buffer;
+                return Ok(buffer);
             }
             Ok(Key::Char(c)) => {
                 buffer.push(c);
@@ -516,7 +952,7 @@ pub fn input(
             Ok(Key::Backspace) | Ok(Key::Delete) => {
                 buffer.pop();
             }
-            Ok(Key::Ctrl('c')) | Ok(Key::Ctrl('z')) => exit(1),
+            Ok(Key::Ctrl('c')) | Ok(Key::Ctrl('z')) => quit(1),
             Ok(_key) => {}
             Err(e) => eprintln!("{e}"),
         }
@@ -539,26 +975,33 @@ pub fn input(
         let _ = stdout.flush();
     }
 }
-fn new_directory() -> Result<(String, SecretString, bool), String> {
-    loop {
-        let directory_name: String = input(
+fn new_directory(
+    breach_database: Option<&BreachDatabase>,
+) -> Result<(String, SecretString, bool), String> {
+    let directories = fs::read_dir(getcwd())
+        .unwrap()
+        .map(|p| p.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<String>>();
+    let directory_name: String = without_lock(|| {
+        input(
             "Directory name: ",
             String::new(),
-            &fs::read_dir(getcwd())
-                .unwrap()
-                .map(|p| p.unwrap().file_name().into_string().unwrap())
-                .collect::<Vec<String>>(),
+            &directories,
             &[
                 InputFlags::IsBlacklist,
                 InputFlags::DenyEmptyInput,
                 InputFlags::HighlightInput,
             ],
-        );
-        let master_password = new_password_input("Master password: ");
-        initialize_directory(&directory_name, master_password.expose_secret())?;
-        println!();
-        return Ok((directory_name, master_password, true));
-    }
+        )
+    });
+    let master_password = without_lock(|| new_password_input("Master password: ", breach_database));
+    initialize_directory(
+        &directory_name,
+        master_password.expose_secret(),
+        &VaultSettings::default(),
+    )?;
+    println!();
+    Ok((directory_name, master_password, true))
 }
 
 /// Gets current working directory
@@ -575,7 +1018,7 @@ pub fn getcwd() -> String {
 /// Shortens home as ~ in the current working directory path
 pub fn getcwd_short() -> String {
     let current_directory: String = getcwd();
-    let home_dir = std::env::vars().find(|key_value| key_value.0 == "HOME".to_string());
+    let home_dir = std::env::vars().find(|key_value| key_value.0 == "HOME");
     if home_dir.is_none() {
         return current_directory;
     }
@@ -597,24 +1040,44 @@ fn check_master_password(directory_name: &str, input: &str) -> bool {
     true
 }
 
+/// The master password from `source` (exits if it's wrong, there's nobody to try again) or from
+/// [prompt_master_password]
+pub fn master_password(directory_name: &str, source: Option<&PasswordSource>) -> SecretString {
+    let Some(source) = source else {
+        return prompt_master_password(directory_name);
+    };
+    match source.read() {
+        Ok(master) if check_master_password(directory_name, master.expose_secret()) => master,
+        Ok(_) => {
+            eprintln!("{RED}Error: The master password from {source} is incorrect{RESET}");
+            exit(exitcode::WRONG_PASSWORD)
+        }
+        Err(error) => {
+            eprintln!("{RED}Error: {error}{RESET}");
+            exit(exitcode::ERROR)
+        }
+    }
+}
+
 pub fn prompt_master_password(directory_name: &str) -> SecretString {
     for _ in 1..=3 {
         let master = password_input("Master password: ");
-        println!();
+        eprintln!();
         if !check_master_password(directory_name, master.expose_secret()) {
             continue;
         }
         return master;
     }
+    leave_alternate_screen();
     eprintln!("3 incorrect password attempts");
-    exit(1)
+    exit(exitcode::WRONG_PASSWORD)
 }
 
 fn process_command(command: &str) {
     if command == "ls" {
         list_directory(&getcwd());
     } else if command == "exit" {
-        exit(0)
+        quit(0)
     } else if command == "clear" {
         println!("{CLEAR}");
     } else if command == "help" {
@@ -625,16 +1088,21 @@ fn process_command(command: &str) {
 }
 
 /// Gives a prompt to the user to choose a directory
-pub fn directory_selector(format_string: String) -> Result<(String, SecretString, bool), String> {
+pub fn directory_selector(
+    format_string: String,
+    breach_database: Option<&BreachDatabase>,
+) -> Result<(String, SecretString, bool), String> {
     let commands = all_commands();
     let mut prompt = directory_selector_prompt(&format_string);
     loop {
-        let usr = input(
-            prompt.clone(),
-            String::new(),
-            &commands,
-            &[InputFlags::HighlightInput],
-        );
+        let usr = without_lock(|| {
+            input(
+                prompt.clone(),
+                String::new(),
+                &commands,
+                &[InputFlags::HighlightInput],
+            )
+        });
         let sp: Vec<&str> = usr.split_whitespace().collect();
         if sp.is_empty() {
             continue;
@@ -645,16 +1113,15 @@ pub fn directory_selector(format_string: String) -> Result<(String, SecretString
                 continue;
             }
             if command == "new" {
-                return Ok(new_directory()?);
+                return new_directory(breach_database);
             }
             process_command(command);
             continue;
         }
         let (command, command_input): (&str, &str) = (process_alias(sp[0]), &sp[1..].join(" "));
         if command == "cd" {
-            let cd_result = std::env::set_current_dir(command_input);
-            if cd_result.is_err() {
-                eprintln!("{}", cd_result.unwrap_err());
+            if let Err(err) = std::env::set_current_dir(command_input) {
+                eprintln!("{err}");
                 continue;
             }
             prompt = directory_selector_prompt(&format_string);
@@ -679,38 +1146,514 @@ pub fn directory_selector(format_string: String) -> Result<(String, SecretString
 }
 
 /// "Press any key to continue..." recreation
-pub fn pause() {
+pub fn pause() -> Result<(), Locked> {
     let getch = Getch::new();
     let mut buf = io::stdout();
     print!("Press any key to continue...");
     let _ = buf.flush();
-    let chr = getch.getch();
+    let chr = read_key(&getch)?;
     match chr {
-        Ok(Key::Ctrl('c')) => exit(1),
+        Ok(Key::Ctrl('c')) => quit(1),
         Ok(_key) => {}
         Err(e) => eprintln!("{e}"),
     }
+    Ok(())
+}
+
+/// Everything about a service line by line, the password is only shown with `reveal`
+pub fn details(
+    password_array: &PasswordArray,
+    service: &str,
+    reveal: bool,
+) -> Result<Vec<String>, String> {
+    let mut lines = vec![];
+    let password = password_array.get_password(service)?;
+    lines.push(format!("Service: {service}"));
+    if reveal {
+        lines.push(format!("Password: {}", password.expose_secret()));
+    } else {
+        lines.push(format!(
+            "Password: {}",
+            "*".repeat(password.expose_secret().chars().count())
+        ));
+    }
+    let tags = password_array.get_tags(service)?;
+    if !tags.is_empty() {
+        lines.push(format!("Tags: {}", tags.join(", ")));
+    }
+    if let Some(changed) = password_array.get_changed(service)? {
+        lines.push(format!("Last changed: {}", rotation::format_date(changed)));
+    }
+    if let (Some(expiry), Some(expires_at)) = (
+        password_array.get_expiry(service)?,
+        password_array.expires_at(service)?,
+    ) {
+        lines.push(format!(
+            "Expiry: {expiry} ({})",
+            rotation::describe(expires_at)
+        ));
+    }
+    if let Some(rules) = password_array.get_password_rules(service)? {
+        lines.push(format!("Password rules: {rules}"));
+    }
+    if let Ok(otp) = password_array.get_otp(service) {
+        let kind = if otp.remaining().is_some() {
+            "TOTP"
+        } else {
+            "HOTP"
+        };
+        lines.push(format!("2FA: {kind}"));
+    }
+    if let Some(pending) = password_array.get_pending(service)? {
+        if reveal {
+            lines.push(format!("Pending password: {}", pending.expose_secret()));
+        } else {
+            lines.push(String::from("Pending password: yes"));
+        }
+    }
+    Ok(lines)
+}
+
+/// Whether the interactive session switched to the alternate screen buffer
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+/// The terminal mode from before the alternate screen, a widget that's left with [quit] (or a
+/// signal) doesn't get to restore it
+#[cfg(unix)]
+static ORIGINAL_TERMINAL_MODE: OnceLock<nix::libc::termios> = OnceLock::new();
+/// Shows the cursor and leaves the alternate screen buffer
+const RESTORE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+
+/// Switches to the alternate screen buffer so nothing shown in the interactive session (like
+/// revealed passwords) ends up in the scrollback of the terminal, [leave_alternate_screen] (or
+/// [quit], a signal or a panic) switches back
+pub fn enter_alternate_screen() {
+    if ALTERNATE_SCREEN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+        if let Ok(mode) = nix::sys::termios::tcgetattr(nix::libc::STDIN_FILENO) {
+            let _ = ORIGINAL_TERMINAL_MODE.set(mode.into());
+        }
+        let action = SigAction::new(
+            SigHandler::Handler(restore_screen_and_exit),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        for signal in [
+            Signal::SIGINT,
+            Signal::SIGTERM,
+            Signal::SIGHUP,
+            Signal::SIGQUIT,
+        ] {
+            // SAFETY: the handler only calls async-signal-safe functions
+            let _ = unsafe { sigaction(signal, &action) };
+        }
+    }
+    static PANIC_HOOK: Once = Once::new();
+    PANIC_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            leave_alternate_screen();
+            hook(info)
+        }));
+    });
+    print!("{}{CLEAR}", Csi::EnterAltScreen);
+    let _ = io::stdout().flush();
+}
+
+/// Restores the terminal mode, shows the cursor and switches back to what was on the screen
+/// before [enter_alternate_screen]
+pub fn leave_alternate_screen() {
+    if !ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    #[cfg(unix)]
+    {
+        use nix::sys::{
+            signal::{SigHandler, Signal, signal},
+            termios::{SetArg, Termios, tcsetattr},
+        };
+        if let Some(mode) = ORIGINAL_TERMINAL_MODE.get() {
+            let _ = tcsetattr(
+                nix::libc::STDIN_FILENO,
+                SetArg::TCSADRAIN,
+                &Termios::from(*mode),
+            );
+        }
+        for sig in [
+            Signal::SIGINT,
+            Signal::SIGTERM,
+            Signal::SIGHUP,
+            Signal::SIGQUIT,
+        ] {
+            // SAFETY: the default handler is always safe
+            let _ = unsafe { signal(sig, SigHandler::SigDfl) };
+        }
+    }
+    print!("{RESTORE_SCREEN}");
+    let _ = io::stdout().flush();
+}
+
+/// Exits the interactive session, use this instead of [exit] so the terminal is restored
+pub fn quit(code: i32) -> ! {
+    leave_alternate_screen();
+    exit(code)
+}
+
+/// Restores the terminal when nspm is interrupted or killed
+#[cfg(unix)]
+extern "C" fn restore_screen_and_exit(signal: nix::libc::c_int) {
+    // SAFETY: tcsetattr, write and _exit are async-signal-safe and the terminal mode isn't changed
+    // after it's set
+    unsafe {
+        if let Some(mode) = ORIGINAL_TERMINAL_MODE.get() {
+            nix::libc::tcsetattr(nix::libc::STDIN_FILENO, nix::libc::TCSANOW, mode);
+        }
+        nix::libc::write(
+            nix::libc::STDOUT_FILENO,
+            RESTORE_SCREEN.as_ptr().cast(),
+            RESTORE_SCREEN.len(),
+        );
+        nix::libc::_exit(128 + signal)
+    }
+}
+
+/// Seconds without a key until the interactive session is locked, 0 never locks
+static LOCK_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+/// Set while [lock_vault] waits for the master password
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Returned by [read_key] (and everything that waits for a key) when nothing was pressed for the
+/// lock timeout, it's passed up to the loop of the session which locks the vault
+#[derive(Debug)]
+pub struct Locked;
+
+/// Locks the interactive session after `minutes` without a key (0 never locks it)
+pub fn set_lock_timeout(minutes: u64) {
+    LOCK_TIMEOUT.store(minutes * 60, Ordering::SeqCst);
+}
+
+/// Asks for `prompt` again until it's answered before the lock timeout, for the prompts outside of
+/// a session (there's no vault to lock)
+pub fn without_lock<T>(mut prompt: impl FnMut() -> Result<T, Locked>) -> T {
+    loop {
+        if let Ok(answer) = prompt() {
+            return answer;
+        }
+    }
+}
+
+/// Forgets the master password (see [PasswordArray::lock]), clears the screen and waits for it
+/// before unlocking the vault again, `idle` is whether it's locked because of the lock timeout
+pub fn lock_vault(password_array: &mut PasswordArray, idle: bool) {
+    password_array.lock();
+    println!("{CLEAR}");
+    LOCKED.store(true, Ordering::SeqCst);
+    let minutes = LOCK_TIMEOUT.load(Ordering::SeqCst) / 60;
+    let plural = if minutes == 1 { "" } else { "s" };
+    let mut message = if idle {
+        format!("Locked after {minutes} minute{plural} without a key")
+    } else {
+        format!("{} is locked", password_array.get_directory())
+    };
+    let master_password = loop {
+        let master_password = password_input(format!("{message}\nMaster password: "));
+        if check_master_password(
+            password_array.get_directory(),
+            master_password.expose_secret(),
+        ) {
+            break master_password;
+        }
+        message = format!("{RED}Incorrect password{RESET}");
+    };
+    LOCKED.store(false, Ordering::SeqCst);
+    password_array.unlock(master_password);
+}
+
+/// [Getch::getch] that returns [Locked] after the lock timeout
+pub fn read_key(getch: &Getch) -> Result<io::Result<Key>, Locked> {
+    loop {
+        if let Some(key) = wait_for_key(getch, None)? {
+            return Ok(key);
+        }
+    }
+}
+
+/// Waits for a key for at most `timeout` (None when it passed), when nothing is pressed for the
+/// lock timeout it returns [Locked]
+///
+/// The wait is interrupted with `SIGALRM` (getch-rs reads from the buffered stdin so it can't be
+/// polled), on other platforms there's no timeout
+pub fn wait_for_key(
+    getch: &Getch,
+    timeout: Option<Duration>,
+) -> Result<Option<io::Result<Key>>, Locked> {
+    #[cfg(unix)]
+    {
+        use nix::{
+            libc::c_int,
+            sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction},
+            unistd::alarm,
+        };
+        extern "C" fn interrupt(_: c_int) {}
+        static ALARM_HANDLER: Once = Once::new();
+        ALARM_HANDLER.call_once(|| {
+            // no SA_RESTART so the read is interrupted
+            let action = SigAction::new(
+                SigHandler::Handler(interrupt),
+                SaFlags::empty(),
+                SigSet::empty(),
+            );
+            // SAFETY: the handler doesn't do anything
+            let _ = unsafe { sigaction(Signal::SIGALRM, &action) };
+        });
+        let lock_timeout = match LOCK_TIMEOUT.load(Ordering::SeqCst) {
+            0 => None,
+            _ if LOCKED.load(Ordering::SeqCst) => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        };
+        let started = Instant::now();
+        loop {
+            let elapsed = started.elapsed();
+            if lock_timeout.is_some_and(|lock_timeout| elapsed >= lock_timeout) {
+                return Err(Locked);
+            }
+            if timeout.is_some_and(|timeout| elapsed >= timeout) {
+                return Ok(None);
+            }
+            if let Some(remaining) = [lock_timeout, timeout]
+                .into_iter()
+                .flatten()
+                .min()
+                .map(|limit| limit - elapsed)
+            {
+                // alarm only has seconds, a second too much is fine
+                alarm::set(remaining.as_secs_f64().ceil().max(1.0) as u32);
+            }
+            let key = getch.getch();
+            alarm::cancel();
+            match key {
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                key => return Ok(Some(key)),
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = timeout;
+        Ok(Some(getch.getch()))
+    }
+}
+
+/// Prints whatever `render` returns and redraws it every second until a key is pressed
+pub fn live_view(render: impl Fn() -> String, footer: &str) -> Result<Option<Key>, Locked> {
+    let mut buf = io::stdout();
+    println!("{}", Csi::Hide);
+    let getch = Getch::new();
+    let key = loop {
+        println!("{CLEAR}");
+        println!("{}", render());
+        print!("{footer}");
+        let _ = buf.flush();
+        match wait_for_key(&getch, Some(Duration::from_secs(1)))? {
+            Some(Ok(Key::Ctrl('c'))) => quit(1),
+            None => {}
+            Some(Ok(key)) => break Some(key),
+            Some(Err(_)) => break None,
+        }
+    };
+    drop(getch);
+    println!("{}", Csi::Show);
+    Ok(key)
 }
 
-/// Generates a random (hopefully) password
-pub fn generate_password(length: u32) -> String {
-    let mut os = StdRng::from_os_rng();
-    let mut generated_password = String::new();
-    for _ in 0..length {
-        generated_password.push_str(CHARS.get(os.random_range(..CHARS.len())).unwrap());
+/// Puts `text` on the clipboard with [Osc::SetClipboard] and clears it after `clear_after`
+/// seconds (never when it's 0), the clearing is done by a background nspm so it still happens
+/// when this one exits
+pub fn copy_to_clipboard(text: &str, clear_after: u64) -> Result<(), String> {
+    write_to_terminal(Osc::SetClipboard(text.to_string()))
+        .map_err(|err| format!("Failed to copy to the clipboard: {err}"))?;
+    if clear_after == 0 {
+        return Ok(());
     }
-    generated_password
+    let executable = std::env::current_exe()
+        .map_err(|err| format!("Can't clear the clipboard later, Error: {err}"))?;
+    let mut command = process::Command::new(executable);
+    command
+        .args(["clear-clipboard", "--after", &clear_after.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Ctrl-C in this nspm shouldn't stop the clearing
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    command
+        .spawn()
+        .map_err(|err| format!("Can't clear the clipboard later, Error: {err}"))?;
+    Ok(())
+}
+
+/// Waits and then overwrites the clipboard with an empty string (what `copy_to_clipboard` starts)
+pub fn clear_clipboard_after(seconds: u64) {
+    thread::sleep(Duration::from_secs(seconds));
+    let _ = write_to_terminal(Osc::SetClipboard(String::new()));
+}
+
+const GENERATOR_TYPES: [&str; 5] = ["random", "passphrase", "rules", "pattern", "pronounceable"];
+
+/// Generates a random (hopefully) password with `generator`
+pub fn generate_password(generator: &Generator) -> Result<String, String> {
+    generator.generate()
+}
+
+/// Asks which [Generator] to use and then for its settings
+pub fn prompt_generator() -> Result<Generator, Locked> {
+    let commands: Vec<String> = GENERATOR_TYPES.iter().map(|s| s.to_string()).collect();
+    let generator_type = input(
+        "Type (random, passphrase, rules, pattern, pronounceable): ",
+        "random".to_string(),
+        &commands,
+        &[InputFlags::HighlightInput],
+    )?;
+    Ok(match generator_type.trim() {
+        "passphrase" => Generator::Passphrase(prompt_passphrase_policy()?),
+        "rules" => Generator::Rules(prompt_password_rules()?),
+        "pattern" => Generator::Pattern(PatternPolicy {
+            template: input(
+                "Pattern (c/C: consonant, v/V: vowel, a/A: letter, 9: digit, !: symbol): ",
+                "Cvccvc-99!".to_string(),
+                NO_COMMANDS,
+                NO_FLAGS,
+            )?,
+        }),
+        "pronounceable" => Generator::Pronounceable(PronounceablePolicy {
+            syllables: prompt_number("Amount of syllables: ", "6".to_string())? as usize,
+            digits: prompt_number("Amount of digits: ", "2".to_string())? as usize,
+            capitalize: confirm("Capitalize the first letter? ", "no")?,
+        }),
+        _ => Generator::Random(prompt_password_policy()?),
+    })
+}
+
+/// Asks for a passwordrules string until it parses
+pub fn prompt_password_rules() -> Result<PasswordRules, Locked> {
+    loop {
+        let rules = input(
+            "passwordrules: ",
+            String::new(),
+            NO_COMMANDS,
+            &[InputFlags::DenyEmptyInput],
+        )?;
+        match PasswordRules::parse(&rules) {
+            Ok(rules) => return Ok(rules),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
+
+/// Asks for the amount of words and (if the user wants to) the rest of a [PassphrasePolicy]
+pub fn prompt_passphrase_policy() -> Result<PassphrasePolicy, Locked> {
+    let mut policy = PassphrasePolicy {
+        words: prompt_number("Amount of words: ", "6".to_string())? as usize,
+        ..Default::default()
+    };
+    if !confirm("Customize the passphrase? ", "no")? {
+        return Ok(policy);
+    }
+    policy.separator = input("Separator: ", "-".to_string(), NO_COMMANDS, NO_FLAGS)?;
+    let commands: Vec<String> = Capitalization::NAMES
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    loop {
+        let capitalization = input(
+            "Capitalization (lower, upper, title, random): ",
+            "lower".to_string(),
+            &commands,
+            &[InputFlags::HighlightInput],
+        )?;
+        match Capitalization::parse(&capitalization) {
+            Ok(c) => {
+                policy.capitalization = c;
+                break;
+            }
+            Err(error) => println!("{error}"),
+        }
+    }
+    policy.digit = confirm("Add a digit? ", "no")?;
+    policy.symbol = confirm("Add a symbol? ", "no")?;
+    loop {
+        let wordlist = input(
+            "Wordlist (large, short or a path to a wordlist): ",
+            "large".to_string(),
+            NO_COMMANDS,
+            NO_FLAGS,
+        )?;
+        match Wordlist::parse(&wordlist) {
+            Ok(w) => {
+                policy.wordlist = w;
+                break;
+            }
+            Err(error) => println!("{error}"),
+        }
+    }
+    Ok(policy)
+}
+
+/// Asks for the length and (if the user wants to) the rest of a [PasswordPolicy]
+pub fn prompt_password_policy() -> Result<PasswordPolicy, Locked> {
+    let mut policy = PasswordPolicy {
+        length: prompt_number("Length of generated password: ", "14".to_string())? as usize,
+        ..Default::default()
+    };
+    if !confirm("Customize the policy? ", "no")? {
+        return Ok(policy);
+    }
+    let classes = input(
+        "Character classes (l: lowercase, u: uppercase, d: digits, s: symbols): ",
+        "luds".to_string(),
+        NO_COMMANDS,
+        NO_FLAGS,
+    )?;
+    policy.classes = vec![];
+    for class in CharClass::ALL {
+        if classes.contains(class.letter()) {
+            let minimum = prompt_number(
+                &format!("Minimum amount of {}: ", class.name()),
+                "1".to_string(),
+            )?;
+            policy.classes.push((class, minimum as usize));
+        }
+    }
+    policy.exclude = input(
+        "Excluded characters: ",
+        String::new(),
+        NO_COMMANDS,
+        NO_FLAGS,
+    )?;
+    policy.no_look_alikes = confirm("Leave out look-alikes (0O1lI)? ", "no")?;
+    policy.no_repeats = confirm("Leave out repeated characters (aa)? ", "no")?;
+    policy.no_sequences = confirm("Leave out sequences (abc, 321)? ", "no")?;
+    Ok(policy)
+}
+
+/// Asks a yes or no question
+pub fn confirm(prompt: &str, default: &str) -> Result<bool, Locked> {
+    let answer = input(prompt, default.to_string(), NO_COMMANDS, NO_FLAGS)?;
+    Ok(YESES.iter().any(|y| *y == answer.to_lowercase().trim()))
 }
 
 /// Prompts the user for a number
-pub fn prompt_number(prompt: &str, default: String) -> u32 {
+pub fn prompt_number(prompt: &str, default: String) -> Result<u32, Locked> {
     loop {
-        let number = input(prompt, default.clone(), NO_COMMANDS, NO_FLAGS);
+        let number = input(prompt, default.clone(), NO_COMMANDS, NO_FLAGS)?;
         if number.bytes().any(|b| !b.is_ascii_digit()) {
             continue;
         }
         match number.parse::<u32>() {
-            Ok(n) => return n,
+            Ok(n) => return Ok(n),
             Err(err) => eprintln!("Error when parsing integer: {err}"),
         }
     }